@@ -1,18 +1,22 @@
 #![feature(test)]
 
-extern crate bloom_filter_wbj;
-use bloom_filter_wbj::BloomFilter;
+extern crate bloom;
+use bloom::{sizing, BitVec, BlockedBloomFilter, BloomFilter, SipState};
+
+use std::hash::{BuildHasher, Hash};
 
 extern crate test;
-use test::Bencher;
 use test::black_box;
+use test::Bencher;
 
 #[bench]
 fn bench_inst_with_size_100_fp_01(bencher: &mut Bencher) {
     let elems = black_box(100);
     let fp = black_box(0.01f32);
 
-    bencher.iter(|| { BloomFilter::new_with_fp(elems, fp); });
+    bencher.iter(|| {
+        BloomFilter::new_with_fp(elems, fp);
+    });
 }
 
 #[bench]
@@ -20,7 +24,9 @@ fn bench_inst_with_size_1000_fp_01(bencher: &mut Bencher) {
     let elems = black_box(1_000);
     let fp = black_box(0.01f32);
 
-    bencher.iter(|| { BloomFilter::new_with_fp(elems, fp); });
+    bencher.iter(|| {
+        BloomFilter::new_with_fp(elems, fp);
+    });
 }
 
 #[bench]
@@ -28,7 +34,9 @@ fn bench_inst_with_size_10000_fp_01(bencher: &mut Bencher) {
     let elems = black_box(10_000);
     let fp = black_box(0.01f32);
 
-    bencher.iter(|| { BloomFilter::new_with_fp(elems, fp); });
+    bencher.iter(|| {
+        BloomFilter::new_with_fp(elems, fp);
+    });
 }
 
 #[bench]
@@ -38,5 +46,119 @@ fn bench_ins_size_100_fp_01(bencher: &mut Bencher) {
     let to_insert = black_box(&[1, 2, 3, 4, 5]);
     let mut filter = BloomFilter::new_with_fp(elems, fp);
 
-    bencher.iter(|| { filter.insert(to_insert); });
+    bencher.iter(|| {
+        filter.insert(to_insert);
+    });
+}
+
+#[bench]
+fn bench_ins_size_100_fp_0001(bencher: &mut Bencher) {
+    let elems = 100;
+    let fp = 0.0001f32;
+    let to_insert = black_box(&[1, 2, 3, 4, 5]);
+    let mut filter = BloomFilter::new_with_fp(elems, fp);
+
+    bencher.iter(|| {
+        filter.insert(to_insert);
+    });
+}
+
+/// Inserts by hashing a member once per hasher, as `BloomFilter` did before
+/// it switched to double hashing, as a baseline for `bench_ins_*`
+struct IndependentHashers {
+    buffer: BitVec,
+    hashers: Vec<SipState>,
+}
+
+impl IndependentHashers {
+    fn new_with_fp(n_elems: usize, fp_rate: f32) -> IndependentHashers {
+        let n_buckets = sizing::min_n_buckets(n_elems, fp_rate);
+        let n_hashers = sizing::optimal_n_hashers(n_buckets, n_elems);
+
+        IndependentHashers {
+            buffer: BitVec::from_elem(n_buckets, false),
+            hashers: (0..n_hashers as u64)
+                .map(|i| SipState::with_keys(i, i))
+                .collect(),
+        }
+    }
+
+    fn insert<T: Hash>(&mut self, e: &T) {
+        for h in &self.hashers {
+            let idx = h.hash_one(e) as usize % self.buffer.len();
+            self.buffer.set(idx, true);
+        }
+    }
+}
+
+#[bench]
+fn bench_ins_independent_size_100_fp_01(bencher: &mut Bencher) {
+    let to_insert = black_box(&[1, 2, 3, 4, 5]);
+    let mut filter = IndependentHashers::new_with_fp(100, 0.01);
+
+    bencher.iter(|| {
+        filter.insert(to_insert);
+    });
+}
+
+#[bench]
+fn bench_ins_independent_size_100_fp_0001(bencher: &mut Bencher) {
+    let to_insert = black_box(&[1, 2, 3, 4, 5]);
+    let mut filter = IndependentHashers::new_with_fp(100, 0.0001);
+
+    bencher.iter(|| {
+        filter.insert(to_insert);
+    });
+}
+
+#[bench]
+fn bench_may_contain_size_1000000_fp_01(bencher: &mut Bencher) {
+    let elems = 1_000_000;
+    let fp = 0.01f32;
+    let mut filter = BloomFilter::new_with_fp(elems, fp);
+    for e in 0..elems {
+        filter.insert(&e);
+    }
+    let to_check = black_box(elems / 2);
+
+    bencher.iter(|| filter.may_contain(&to_check));
+}
+
+#[bench]
+fn bench_may_contain_absent_size_1000000_fp_01(bencher: &mut Bencher) {
+    let elems = 1_000_000;
+    let fp = 0.01f32;
+    let mut filter = BloomFilter::new_with_fp(elems, fp);
+    for e in 0..elems {
+        filter.insert(&e);
+    }
+    let to_check = black_box(elems * 2);
+
+    bencher.iter(|| filter.may_contain(&to_check));
+}
+
+#[bench]
+fn bench_blocked_may_contain_size_1000000_fp_01(bencher: &mut Bencher) {
+    let elems = 1_000_000;
+    let fp = 0.01f32;
+    let mut filter = BlockedBloomFilter::new_with_fp(elems, fp);
+    for e in 0..elems {
+        filter.insert(&e);
+    }
+    let to_check = black_box(elems / 2);
+
+    bencher.iter(|| filter.may_contain(&to_check));
+}
+
+#[bench]
+fn bench_blocked_may_contain_absent_size_1000000_fp_01(bencher: &mut Bencher) {
+    let elems = 1_000_000;
+    let fp = 0.01f32;
+    let mut filter = BlockedBloomFilter::new_with_fp(elems, fp);
+    for e in 0..elems {
+        filter.insert(&e);
+    }
+    let to_check = black_box(elems * 2);
+
+    bencher.iter(|| filter.may_contain(&to_check));
 }
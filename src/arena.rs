@@ -0,0 +1,214 @@
+//! Many small Bloom Filters sharing one buffer
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use bit_vec::BitVec;
+
+use super::{check_fp_rate, min_n_buckets, optimal_n_hashers};
+use super::{indexes_are_set, set_indexes, Hashers, SipState};
+
+/// Identifies one of the filters in a `BloomFilterArena`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FilterId(usize);
+
+/// Many Bloom Filters of the same size packed into a single buffer
+///
+/// Every filter is a fixed-size slice of one shared buffer, with hashers of
+/// its own, so a member of one filter never sets buckets of another. Each
+/// filter only costs its buckets and its hashers, rather than a separate
+/// allocation, which dominates for thousands of tiny filters.
+#[derive(Debug, Clone)]
+pub struct BloomFilterArena {
+    buffer: BitVec,
+    slice_len: usize,
+    n_hashers: usize,
+    hashers: Vec<Hashers<SipState>>,
+}
+
+impl BloomFilterArena {
+    /// Build an empty arena for filters with a specified false positive rate
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements of each filter
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_fp(n_elems: usize, fp_rate: f32) -> BloomFilterArena {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        BloomFilterArena::new_with_size(
+            n_elems,
+            min_n_buckets(n_elems, fp_rate),
+        )
+    }
+
+    /// Build an empty arena for filters with a specified number of buckets
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements of each filter
+    /// * `size`: number of buckets of each filter, rounded up to 1 if zero
+    pub fn new_with_size(n_elems: usize, size: usize) -> BloomFilterArena {
+        let size = size.max(1);
+
+        BloomFilterArena {
+            buffer: BitVec::new(),
+            slice_len: size,
+            n_hashers: optimal_n_hashers(size, n_elems),
+            hashers: Vec::new(),
+        }
+    }
+
+    /// Add an empty filter with new random hashers, returning its id
+    pub fn add_filter(&mut self) -> FilterId {
+        self.buffer.grow(self.slice_len, false);
+        self.hashers
+            .push(Hashers::new(self.n_hashers, SipState::new));
+        FilterId(self.hashers.len() - 1)
+    }
+
+    /// Insert a member into one of the filters
+    ///
+    /// Returns whether any of the member's buckets were previously unset,
+    /// i.e. whether it was definitely not a member of that filter before.
+    ///
+    /// # Arguments
+    /// * `id`: filter to insert into
+    /// * `e`: element to add
+    ///
+    /// # Panics
+    /// If `id` isn't a filter of this arena
+    pub fn insert<T>(&mut self, id: FilterId, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        let indexes = self.indexes(id, e);
+        set_indexes(&mut self.buffer, indexes)
+    }
+
+    /// Check membership of one of the filters
+    ///
+    /// # Arguments
+    /// * `id`: filter to check membership of
+    /// * `e`: element to check membership of
+    ///
+    /// # Panics
+    /// If `id` isn't a filter of this arena
+    pub fn may_contain<T>(&self, id: FilterId, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        indexes_are_set(&self.buffer, self.indexes(id, e))
+    }
+
+    /// Remove every member of one of the filters, keeping its hashers
+    ///
+    /// # Arguments
+    /// * `id`: filter to clear
+    ///
+    /// # Panics
+    /// If `id` isn't a filter of this arena
+    pub fn clear(&mut self, id: FilterId) {
+        let start = self.start(id);
+        for idx in start..start + self.slice_len {
+            self.buffer.set(idx, false);
+        }
+    }
+
+    /// Number of filters in the arena
+    pub fn len(&self) -> usize {
+        self.hashers.len()
+    }
+
+    /// Whether the arena has no filters
+    pub fn is_empty(&self) -> bool {
+        self.hashers.is_empty()
+    }
+
+    /// Number of buckets of each filter
+    pub fn buckets(&self) -> usize {
+        self.slice_len
+    }
+
+    /// Number of hashers of each filter
+    pub fn n_hashers(&self) -> usize {
+        self.n_hashers
+    }
+
+    /// Index of the first bucket of a filter
+    fn start(&self, id: FilterId) -> usize {
+        assert!(id.0 < self.len(), "no filter {} in the arena", id.0);
+        id.0 * self.slice_len
+    }
+
+    /// The indexes that a element hashes to in a filter
+    fn indexes<T>(&self, id: FilterId, e: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        let start = self.start(id);
+        self.hashers[id.0]
+            .indexes(e, self.slice_len)
+            .map(move |idx| start + idx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filters_are_isolated() {
+        let mut arena = BloomFilterArena::new_with_fp(100, 0.01);
+        let ids: Vec<_> = (0..3).map(|_| arena.add_filter()).collect();
+        assert_eq!(3, arena.len());
+
+        for (i, &id) in ids.iter().enumerate() {
+            for e in i * 100..(i + 1) * 100 {
+                arena.insert(id, &e);
+            }
+        }
+
+        for (i, &id) in ids.iter().enumerate() {
+            assert!((i * 100..(i + 1) * 100).all(|e| arena.may_contain(id, &e)));
+
+            let others = (0..300).filter(|e| e / 100 != i);
+            let fps = others.filter(|e| arena.may_contain(id, e)).count();
+            assert!(fps < 20, "{} false positives", fps);
+        }
+    }
+
+    #[test]
+    fn test_clear_one_filter() {
+        let mut arena = BloomFilterArena::new_with_size(10, 100);
+        let a = arena.add_filter();
+        let b = arena.add_filter();
+        arena.insert(a, &"in a");
+        arena.insert(b, &"in b");
+
+        arena.clear(a);
+        assert!(!arena.may_contain(a, &"in a"));
+        assert!(arena.may_contain(b, &"in b"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_fp_out_of_range() {
+        BloomFilterArena::new_with_fp(10, 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unknown_filter() {
+        let mut other = BloomFilterArena::new_with_size(10, 100);
+        other.add_filter();
+        let id = other.add_filter();
+
+        let mut arena = BloomFilterArena::new_with_size(10, 100);
+        arena.add_filter();
+        arena.insert(id, &"nowhere");
+    }
+}
@@ -0,0 +1,195 @@
+//! A Bloom Filter that many threads can insert into at once
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use super::{
+    check_fp_rate, false_positive_rate, min_n_buckets, optimal_n_hashers,
+};
+use super::{Hashers, SipState};
+
+/// Number of buckets in each word of the buffer
+const WORD_BITS: usize = 64;
+
+/// Bloom Filter that can be inserted into through a shared reference
+///
+/// Buckets are set with an atomic `fetch_or` on the word holding them, so
+/// inserts are lock-free and the filter can be shared between threads, e.g.
+/// behind an `Arc`, without a `Mutex`. Only buckets are ever set, never
+/// cleared, so every ordering is `Relaxed`: a member is found by any lookup
+/// that happens after its insert has returned.
+///
+/// Only available on targets with 64-bit atomics.
+#[derive(Debug)]
+pub struct AtomicBloomFilter {
+    words: Vec<AtomicU64>,
+    n_buckets: usize,
+    size: AtomicUsize,
+    hashers: Hashers<SipState>,
+}
+
+impl AtomicBloomFilter {
+    /// Build an Atomic Bloom Filter with a specified false positive rate
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_fp(n_elems: usize, fp_rate: f32) -> AtomicBloomFilter {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        AtomicBloomFilter::new_with_size(
+            n_elems,
+            min_n_buckets(n_elems, fp_rate),
+        )
+    }
+
+    /// Create a new Atomic Bloom Filter with specified number of buckets
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `size`: desired number of buckets, rounded up to 1 if zero
+    pub fn new_with_size(n_elems: usize, size: usize) -> AtomicBloomFilter {
+        let n_buckets = size.max(1);
+        let n_hashers = optimal_n_hashers(n_buckets, n_elems);
+
+        AtomicBloomFilter {
+            words: (0..n_buckets.div_ceil(WORD_BITS))
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            n_buckets,
+            size: AtomicUsize::new(0),
+            hashers: Hashers::new(n_hashers, SipState::new),
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// Returns whether at least one new bit was set by this insert. When
+    /// threads insert the same member at once, at least one of them sees
+    /// `true`.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        let mut is_new = false;
+        for idx in self.hashers.indexes(e, self.n_buckets) {
+            let mask = 1 << (idx % WORD_BITS);
+            let prev =
+                self.words[idx / WORD_BITS].fetch_or(mask, Ordering::Relaxed);
+            is_new |= prev & mask == 0;
+        }
+
+        if is_new {
+            self.size.fetch_add(1, Ordering::Relaxed);
+        }
+
+        is_new
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.hashers.indexes(e, self.n_buckets).all(|idx| {
+            let mask = 1 << (idx % WORD_BITS);
+            self.words[idx / WORD_BITS].load(Ordering::Relaxed) & mask != 0
+        })
+    }
+
+    /// Remove every member
+    pub fn clear(&mut self) {
+        for word in &mut self.words {
+            *word.get_mut() = 0;
+        }
+        *self.size.get_mut() = 0;
+    }
+
+    /// Approximate number of distinct elements in the `AtomicBloomFilter`
+    pub fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// Whether no elements have been inserted
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Number of buckets that a member can occupy
+    pub fn buckets(&self) -> usize {
+        self.n_buckets
+    }
+
+    /// Number of hashers being used
+    pub fn n_hashers(&self) -> usize {
+        self.hashers.len()
+    }
+
+    /// False positive rate
+    pub fn fp_rate(&self) -> f32 {
+        false_positive_rate(self.buckets(), self.n_hashers(), self.size())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::sync::Arc;
+    use std::thread;
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_fp_out_of_range() {
+        AtomicBloomFilter::new_with_fp(10, 0.0);
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        let filter = Arc::new(AtomicBloomFilter::new_with_fp(4000, 0.01));
+
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let filter = Arc::clone(&filter);
+                thread::spawn(move || {
+                    for e in t * 1000..(t + 1) * 1000 {
+                        filter.insert(&e);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert!((0..4000).all(|e| filter.may_contain(&e)));
+        assert!(filter.size() <= 4000 && filter.size() > 3900);
+
+        let false_positives =
+            (4000..14_000).filter(|e| filter.may_contain(e)).count();
+        assert!(false_positives < 150);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut filter = AtomicBloomFilter::new_with_size(10, 100);
+        assert!(filter.insert(&"do add this"));
+        assert!(!filter.insert(&"do add this"));
+
+        filter.clear();
+        assert!(filter.is_empty());
+        assert!(!filter.may_contain(&"do add this"));
+    }
+}
@@ -0,0 +1,210 @@
+//! A Bloom Filter variant that touches one cache line per operation
+
+use core::hash::BuildHasher;
+use core::hash::Hash;
+
+use bit_vec::BitVec;
+
+use super::hashing::reduce;
+use super::{
+    check_fp_rate, false_positive_rate, min_n_buckets, optimal_n_hashers,
+};
+use super::{indexes_are_set, set_indexes, Hashers, SipState};
+
+/// Number of buckets in a block, one 64 byte cache line
+const BLOCK_BITS: usize = 512;
+
+/// Bloom Filter whose members each occupy buckets within a single block
+///
+/// The buffer is split into cache-line-sized blocks. One hash selects a
+/// block and the member's indexes are all chosen within it, so an insert or
+/// lookup costs a single cache miss. Buckets within a block fill unevenly, so
+/// the false positive rate is slightly higher than that of a `BloomFilter`
+/// with the same number of buckets.
+#[derive(Debug, Clone)]
+pub struct BlockedBloomFilter {
+    buffer: BitVec,
+    size: usize,
+    block_state: SipState,
+    hashers: Hashers<SipState>,
+}
+
+impl BlockedBloomFilter {
+    /// Build a Blocked Bloom Filter with a specified false positive rate
+    ///
+    /// The rate is that of an equally sized `BloomFilter`, so the realised
+    /// rate will be slightly higher.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_fp(n_elems: usize, fp_rate: f32) -> BlockedBloomFilter {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        BlockedBloomFilter::new_with_size(
+            n_elems,
+            min_n_buckets(n_elems, fp_rate),
+        )
+    }
+
+    /// Create a new Blocked Bloom Filter with specified number of buckets
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `size`: desired number of buckets, rounded up to a whole number of
+    ///   512 bucket blocks
+    pub fn new_with_size(n_elems: usize, size: usize) -> BlockedBloomFilter {
+        let n_blocks = size.div_ceil(BLOCK_BITS).max(1);
+        let n_buckets = n_blocks * BLOCK_BITS;
+        let n_hashers = optimal_n_hashers(n_buckets, n_elems);
+
+        BlockedBloomFilter {
+            buffer: BitVec::from_elem(n_buckets, false),
+            size: 0,
+            block_state: SipState::new(),
+            hashers: Hashers::new(n_hashers, SipState::new),
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// Returns whether any of the member's buckets were previously unset,
+    /// i.e. whether it was definitely not a member before.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        let indexes = self.indexes(e);
+        let inserted = set_indexes(&mut self.buffer, indexes);
+
+        if inserted {
+            self.size += 1;
+        }
+
+        inserted
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        indexes_are_set(&self.buffer, self.indexes(e))
+    }
+
+    /// Remove all members
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.size = 0;
+    }
+
+    /// Approximate number of distinct elements in the `BlockedBloomFilter`
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether no elements have been inserted
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Number of buckets, always a multiple of the block size
+    pub fn buckets(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Number of hashers
+    pub fn n_hashers(&self) -> usize {
+        self.hashers.len()
+    }
+
+    /// False positive rate of an equally sized `BloomFilter`
+    ///
+    /// A lower bound on the real rate, which also depends on how evenly
+    /// members spread across blocks.
+    pub fn fp_rate(&self) -> f32 {
+        false_positive_rate(self.buckets(), self.n_hashers(), self.size)
+    }
+
+    /// The indexes that an element hashes to, all within one block
+    fn indexes<T>(&self, e: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        let n_blocks = self.buffer.len() / BLOCK_BITS;
+        let block = reduce(self.block_state.hash_one(e), n_blocks);
+
+        self.hashers
+            .indexes(e, BLOCK_BITS)
+            .map(move |idx| block * BLOCK_BITS + idx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_fp_out_of_range() {
+        BlockedBloomFilter::new_with_fp(10, 0.0);
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let mut filter = BlockedBloomFilter::new_with_fp(10_000, 0.01);
+        for e in 0..10_000 {
+            filter.insert(&e);
+        }
+
+        assert!((0..10_000).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_fp_rate_is_close() {
+        let mut filter = BlockedBloomFilter::new_with_fp(10_000, 0.01);
+        for e in 0..10_000 {
+            filter.insert(&e);
+        }
+
+        let false_positives =
+            (10_000..110_000).filter(|e| filter.may_contain(e)).count();
+        assert!((false_positives as f32 / 100_000.) < 0.02);
+    }
+
+    #[test]
+    fn test_indexes_share_a_block() {
+        let filter = BlockedBloomFilter::new_with_size(10, 10 * BLOCK_BITS);
+
+        for e in 0..100 {
+            let blocks: Vec<_> =
+                filter.indexes(&e).map(|idx| idx / BLOCK_BITS).collect();
+            assert!(blocks.iter().all(|&block| block == blocks[0]));
+        }
+    }
+
+    #[test]
+    fn test_size_is_rounded_to_blocks() {
+        assert_eq!(
+            BLOCK_BITS,
+            BlockedBloomFilter::new_with_size(1, 0).buckets()
+        );
+        assert_eq!(
+            2 * BLOCK_BITS,
+            BlockedBloomFilter::new_with_size(1, BLOCK_BITS + 1).buckets()
+        );
+    }
+}
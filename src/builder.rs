@@ -0,0 +1,339 @@
+//! Building a `BloomFilter` from any combination of parameters
+
+use alloc::vec::Vec;
+use bit_vec::BitVec;
+
+use super::SipState;
+use super::{check_fp_rate, false_positive_rate, min_n_buckets};
+use super::{optimal_n_hashers, BloomFilter, BuildError, IndexStrategy};
+
+/// Builds a `BloomFilter`, deriving whichever parameters aren't given
+///
+/// The number of buckets is derived from the expected elements and false
+/// positive rate, and the number of hashers from the buckets and expected
+/// elements. When the expected elements and false positive rate are both
+/// given, the filter must achieve that rate at least as well as one sized
+/// entirely by the formulas would.
+///
+/// ```
+/// use bloom::BloomFilterBuilder;
+///
+/// let filter = BloomFilterBuilder::new()
+///     .expected_elements(1000)
+///     .false_positive_rate(0.01)
+///     .buckets(20_000)
+///     .build()
+///     .unwrap();
+/// assert_eq!(20_000, filter.buckets());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BloomFilterBuilder {
+    n_elems: Option<usize>,
+    fp_rate: Option<f32>,
+    n_buckets: Option<usize>,
+    n_hashers: Option<usize>,
+    strategy: IndexStrategy,
+    salt: Option<u64>,
+    auto_grow: bool,
+}
+
+impl BloomFilterBuilder {
+    /// Create a builder with no parameters set
+    pub fn new() -> BloomFilterBuilder {
+        BloomFilterBuilder::default()
+    }
+
+    /// Set the expected number of elements
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    pub fn expected_elements(mut self, n_elems: usize) -> BloomFilterBuilder {
+        self.n_elems = Some(n_elems);
+        self
+    }
+
+    /// Set the desired false positive rate
+    ///
+    /// # Arguments
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    pub fn false_positive_rate(mut self, fp_rate: f32) -> BloomFilterBuilder {
+        self.fp_rate = Some(fp_rate);
+        self
+    }
+
+    /// Set the number of buckets
+    ///
+    /// # Arguments
+    /// * `n_buckets`: number of buckets
+    pub fn buckets(mut self, n_buckets: usize) -> BloomFilterBuilder {
+        self.n_buckets = Some(n_buckets);
+        self
+    }
+
+    /// Set the number of hashers
+    ///
+    /// # Arguments
+    /// * `n_hashers`: number of hashers
+    pub fn hashers(mut self, n_hashers: usize) -> BloomFilterBuilder {
+        self.n_hashers = Some(n_hashers);
+        self
+    }
+
+    /// Set how indexes are derived from a member's hashes
+    ///
+    /// `IndexStrategy::BitMask` needs the number of buckets to be set to a
+    /// power of two.
+    ///
+    /// # Arguments
+    /// * `strategy`: index strategy, `IndexStrategy::MultiplyShift` if unset
+    pub fn index_strategy(
+        mut self,
+        strategy: IndexStrategy,
+    ) -> BloomFilterBuilder {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Set a salt to hash before every member, see
+    /// `BloomFilter::new_with_salt`
+    ///
+    /// # Arguments
+    /// * `salt`: hashed ahead of every member
+    pub fn salt(mut self, salt: u64) -> BloomFilterBuilder {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Set whether the filter grows once it holds too many elements, see
+    /// `BloomFilter::auto_grow`
+    ///
+    /// Growing needs the number of elements the filter is designed for, so
+    /// the expected number of elements must be set, and be at least 1.
+    ///
+    /// # Arguments
+    /// * `auto_grow`: whether to grow, false if unset
+    pub fn auto_grow(mut self, auto_grow: bool) -> BloomFilterBuilder {
+        self.auto_grow = auto_grow;
+        self
+    }
+
+    /// Build the filter
+    ///
+    /// Fails if a parameter is invalid, if there isn't enough to derive the
+    /// rest, or if the parameters can't achieve the false positive rate. The
+    /// filter is designed for the given false positive rate if there is one,
+    /// and otherwise for the rate at the expected number of elements, which
+    /// is zero if not given.
+    pub fn build(&self) -> Result<BloomFilter, BuildError> {
+        if let Some(fp_rate) = self.fp_rate {
+            check_fp_rate(fp_rate)?;
+        }
+
+        let n_buckets = match (self.n_buckets, self.n_elems, self.fp_rate) {
+            (Some(n_buckets), _, _) => n_buckets,
+            (None, Some(n_elems), Some(fp_rate)) => {
+                min_n_buckets(n_elems, fp_rate)
+            }
+            _ => return Err(BuildError::MissingParameters),
+        };
+
+        let n_hashers = match (self.n_hashers, self.n_elems) {
+            (Some(n_hashers), _) => n_hashers,
+            (None, Some(n_elems)) => optimal_n_hashers(n_buckets, n_elems),
+            _ => return Err(BuildError::MissingParameters),
+        };
+
+        if self.strategy == IndexStrategy::BitMask
+            && !n_buckets.is_power_of_two()
+        {
+            return Err(BuildError::ConflictingParameters);
+        }
+
+        if let (Some(n_elems), Some(fp_rate)) = (self.n_elems, self.fp_rate) {
+            let min_buckets = min_n_buckets(n_elems, fp_rate);
+            let target = false_positive_rate(
+                min_buckets,
+                optimal_n_hashers(min_buckets, n_elems),
+                n_elems,
+            );
+
+            if false_positive_rate(n_buckets, n_hashers, n_elems) > target {
+                return Err(BuildError::ConflictingParameters);
+            }
+        }
+
+        let mut filter = BloomFilter::from_parts(
+            BitVec::from_elem(n_buckets, false),
+            0,
+            [SipState::new(), SipState::new()],
+            n_hashers,
+        )?;
+        filter.hashers = filter.hashers.with_strategy(self.strategy);
+        if let Some(salt) = self.salt {
+            filter.hashers = filter.hashers.with_salt(salt);
+        }
+        filter.capacity = self.n_elems.unwrap_or(0);
+        filter.design_fp_rate = match (self.n_elems, self.fp_rate) {
+            (_, Some(fp_rate)) => fp_rate,
+            (Some(n_elems), None) => {
+                false_positive_rate(n_buckets, n_hashers, n_elems)
+            }
+            (None, None) => 0.,
+        };
+
+        if self.auto_grow {
+            if filter.capacity == 0 || filter.design_fp_rate <= 0. {
+                return Err(BuildError::MissingParameters);
+            }
+            filter.grow_log = Some(Vec::new());
+        }
+
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_by_fp_rate() {
+        let filter = BloomFilterBuilder::new()
+            .expected_elements(1000)
+            .false_positive_rate(0.01)
+            .build()
+            .unwrap();
+        let expected = BloomFilter::new_with_fp(1000, 0.01);
+
+        assert_eq!(expected.buckets(), filter.buckets());
+        assert_eq!(expected.n_hashers(), filter.n_hashers());
+        assert_eq!(0.01, filter.design_fp_rate());
+    }
+
+    #[test]
+    fn test_build_by_buckets_and_hashers() {
+        let mut filter = BloomFilterBuilder::new()
+            .buckets(1000)
+            .hashers(3)
+            .build()
+            .unwrap();
+
+        assert_eq!(1000, filter.buckets());
+        assert_eq!(3, filter.n_hashers());
+        assert_eq!(IndexStrategy::MultiplyShift, filter.index_strategy());
+
+        filter.insert(&"do add this");
+        assert!(filter.may_contain(&"do add this"));
+    }
+
+    #[test]
+    fn test_build_with_strategy() {
+        let filter = BloomFilterBuilder::new()
+            .buckets(1000)
+            .hashers(3)
+            .index_strategy(IndexStrategy::Modulo)
+            .build()
+            .unwrap();
+
+        assert_eq!(IndexStrategy::Modulo, filter.index_strategy());
+    }
+
+    #[test]
+    fn test_build_with_salt() {
+        let builder = BloomFilterBuilder::new().buckets(1000).hashers(3);
+
+        assert_eq!(None, builder.build().unwrap().salt());
+        assert_eq!(Some(7), builder.salt(7).build().unwrap().salt());
+    }
+
+    #[test]
+    fn test_build_bit_mask() {
+        let builder = BloomFilterBuilder::new()
+            .hashers(3)
+            .index_strategy(IndexStrategy::BitMask);
+
+        assert!(builder.buckets(1024).build().is_ok());
+        assert_eq!(
+            Err(BuildError::ConflictingParameters),
+            builder.buckets(1000).build().map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_build_auto_grow() {
+        let builder = BloomFilterBuilder::new().buckets(1000).hashers(3);
+
+        assert!(!builder.build().unwrap().auto_grow());
+        assert_eq!(
+            Err(BuildError::MissingParameters),
+            builder.auto_grow(true).build().map(|_| ())
+        );
+        assert_eq!(
+            Err(BuildError::MissingParameters),
+            builder
+                .false_positive_rate(0.01)
+                .auto_grow(true)
+                .build()
+                .map(|_| ())
+        );
+
+        let mut filter = builder
+            .expected_elements(100)
+            .auto_grow(true)
+            .build()
+            .unwrap();
+        assert!(filter.auto_grow());
+        assert_eq!(Ok(true), filter.try_insert(&"do add this"));
+    }
+
+    #[test]
+    fn test_build_conflicting() {
+        let builder = BloomFilterBuilder::new()
+            .expected_elements(1000)
+            .false_positive_rate(0.01)
+            .buckets(100);
+
+        assert_eq!(
+            Err(BuildError::ConflictingParameters),
+            builder.build().map(|_| ())
+        );
+        assert!(builder.buckets(20_000).build().is_ok());
+    }
+
+    #[test]
+    fn test_build_missing() {
+        assert_eq!(
+            Err(BuildError::MissingParameters),
+            BloomFilterBuilder::new().build().map(|_| ())
+        );
+        assert_eq!(
+            Err(BuildError::MissingParameters),
+            BloomFilterBuilder::new().buckets(100).build().map(|_| ())
+        );
+        assert_eq!(
+            Err(BuildError::MissingParameters),
+            BloomFilterBuilder::new()
+                .false_positive_rate(0.01)
+                .build()
+                .map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let builder = BloomFilterBuilder::new().buckets(100).hashers(3);
+
+        assert_eq!(
+            Err(BuildError::InvalidFpRate),
+            builder.false_positive_rate(1.5).build().map(|_| ())
+        );
+        assert_eq!(
+            Err(BuildError::NoBuckets),
+            builder.buckets(0).build().map(|_| ())
+        );
+        assert_eq!(
+            Err(BuildError::NoHashers),
+            builder.hashers(0).build().map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,628 @@
+//! Compact binary encodings of a `BloomFilter`
+//!
+//! Both encodings start with a header. All integers in it are little-endian.
+//!
+//! | field         | size |
+//! |---------------|------|
+//! | magic         | 4    |
+//! | version       | 1    |
+//! | buckets       | 8    |
+//! | size          | 8    |
+//! | capacity      | 8    |
+//! | hasher count  | 4    |
+//! | strategy      | 1    |
+//! | design rate   | 4    |
+//! | hasher keys   | 32   |
+//! | salted        | 1    |
+//! | salt          | 8    |
+//!
+//! The dense encoding has the magic `BLMF` and is followed by the packed bit
+//! buffer, `ceil(buckets / 8)` bytes. The sparse encoding has the magic
+//! `BLMS` and is followed by the number of set buckets and then the gaps
+//! between their indexes, starting from 0, all as LEB128 varints.
+
+use alloc::vec::Vec;
+
+use bit_vec::BitVec;
+
+use super::{BloomFilter, DecodeError, Hashers, IndexStrategy, SipState};
+
+const MAGIC: &[u8; 4] = b"BLMF";
+const SPARSE_MAGIC: &[u8; 4] = b"BLMS";
+const VERSION: u8 = 1;
+
+/// Length of the header of either encoding
+const HEADER_LEN: usize = 79;
+
+/// Most hashers a decoded filter may have, since every lookup hashes once per
+/// hasher
+const MAX_HASHERS: usize = 256;
+
+/// Most buckets `from_sparse_bytes` decodes, 512 MiB of buffer
+const DEFAULT_MAX_SPARSE_BUCKETS: usize = (u32::MAX as usize).saturating_add(1);
+
+impl BloomFilter {
+    /// Encode the filter into bytes that `from_bytes` can decode
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header(MAGIC);
+        bytes.extend_from_slice(&self.buffer.to_bytes());
+        bytes
+    }
+
+    /// Encode the filter into bytes that `from_sparse_bytes` can decode
+    ///
+    /// Filters with few buckets set are encoded as the indexes of those
+    /// buckets, which takes much less space than the whole buffer. Whichever
+    /// of this and the dense encoding of `to_bytes` is smaller is used.
+    pub fn to_sparse_bytes(&self) -> Vec<u8> {
+        let dense_len = HEADER_LEN + n_bytes(self.buckets());
+
+        let mut bytes = self.header(SPARSE_MAGIC);
+        write_varint(&mut bytes, self.set_bits() as u64);
+        let mut prev = 0;
+        for idx in self.set_bit_indices() {
+            write_varint(&mut bytes, (idx - prev) as u64);
+            prev = idx;
+
+            if bytes.len() >= dense_len {
+                return self.to_bytes();
+            }
+        }
+
+        bytes
+    }
+
+    /// Decode a filter encoded by `to_bytes`
+    ///
+    /// Filters with more than 256 hashers are refused with
+    /// `DecodeError::TooLarge`.
+    ///
+    /// # Arguments
+    /// * `data`: encoded filter
+    pub fn from_bytes(data: &[u8]) -> Result<BloomFilter, DecodeError> {
+        let mut reader = Reader { data };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+
+        reader.read_dense()
+    }
+
+    /// Decode a filter encoded by `to_sparse_bytes` or `to_bytes`
+    ///
+    /// The length of a sparse encoding doesn't bound the number of buckets,
+    /// so sparse encodings of more than 2^32 buckets, 512 MiB of buffer, are
+    /// refused with `DecodeError::TooLarge`, as are filters with more than
+    /// 256 hashers. See `from_sparse_bytes_with_limit` for larger filters.
+    ///
+    /// # Arguments
+    /// * `data`: encoded filter
+    pub fn from_sparse_bytes(data: &[u8]) -> Result<BloomFilter, DecodeError> {
+        BloomFilter::from_sparse_bytes_with_limit(
+            data,
+            DEFAULT_MAX_SPARSE_BUCKETS,
+        )
+    }
+
+    /// Decode a filter encoded by `to_sparse_bytes` or `to_bytes`, refusing
+    /// sparse encodings of more than `max_buckets` buckets
+    ///
+    /// # Arguments
+    /// * `data`: encoded filter
+    /// * `max_buckets`: most buckets a sparse encoding may have
+    pub fn from_sparse_bytes_with_limit(
+        data: &[u8],
+        max_buckets: usize,
+    ) -> Result<BloomFilter, DecodeError> {
+        let mut reader = Reader { data };
+
+        match reader.take(MAGIC.len())? {
+            magic if magic == MAGIC => reader.read_dense(),
+            magic if magic == SPARSE_MAGIC => reader.read_sparse(max_buckets),
+            _ => Err(DecodeError::BadMagic),
+        }
+    }
+
+    /// The header of an encoding with the given magic
+    fn header(&self, magic: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(magic);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.buckets() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.size as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.capacity as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.n_hashers() as u32).to_le_bytes());
+        bytes.push(match self.index_strategy() {
+            IndexStrategy::Modulo => 0,
+            IndexStrategy::MultiplyShift => 1,
+            IndexStrategy::DoubleHash => 2,
+            IndexStrategy::BitMask => 3,
+        });
+        bytes.extend_from_slice(&self.design_fp_rate.to_bits().to_le_bytes());
+        for state in self.hashers.states() {
+            let (k0, k1) = state.keys();
+            bytes.extend_from_slice(&k0.to_le_bytes());
+            bytes.extend_from_slice(&k1.to_le_bytes());
+        }
+        bytes.push(self.salt().is_some() as u8);
+        bytes.extend_from_slice(&self.salt().unwrap_or(0).to_le_bytes());
+        bytes
+    }
+}
+
+/// Number of bytes needed to pack a buffer of bits
+pub(crate) fn n_bytes(buckets: usize) -> usize {
+    buckets.div_ceil(8)
+}
+
+/// A buffer of `n` zero bytes, allocated fallibly so that a size read from
+/// corrupt data can't abort the process
+pub(crate) fn zeroed_bytes(n: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut bytes = Vec::new();
+    if bytes.try_reserve_exact(n).is_err() {
+        return Err(DecodeError::Corrupt);
+    }
+    bytes.resize(n, 0);
+    Ok(bytes)
+}
+
+/// Append an integer as a LEB128 varint
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+/// Everything in the header after the magic
+struct Header {
+    buckets: usize,
+    size: usize,
+    capacity: usize,
+    n_hashers: usize,
+    strategy: IndexStrategy,
+    design_fp_rate: f32,
+    states: [SipState; 2],
+    salt: Option<u64>,
+}
+
+impl Header {
+    /// A filter with this header and bit buffer
+    fn into_filter(self, buffer: BitVec) -> BloomFilter {
+        let mut hashers = Hashers::from_states(self.states, self.n_hashers)
+            .with_strategy(self.strategy);
+        if let Some(salt) = self.salt {
+            hashers = hashers.with_salt(salt);
+        }
+
+        BloomFilter {
+            buffer,
+            size: self.size,
+            capacity: self.capacity,
+            design_fp_rate: self.design_fp_rate,
+            hashers,
+            grow_log: None,
+        }
+    }
+}
+
+/// Consumes an encoded filter from the front
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if self.data.len() < n {
+            return Err(DecodeError::Truncated);
+        }
+
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_usize(&mut self) -> Result<usize, DecodeError> {
+        let n = self.read_u64()?;
+        if n > usize::MAX as u64 {
+            return Err(DecodeError::Corrupt);
+        }
+        Ok(n as usize)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn read_varint(&mut self) -> Result<u64, DecodeError> {
+        let mut n = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            let bits = u64::from(byte & 0x7f);
+            if bits << shift >> shift != bits {
+                return Err(DecodeError::Corrupt);
+            }
+
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+
+        Err(DecodeError::Corrupt)
+    }
+
+    fn read_header(&mut self) -> Result<Header, DecodeError> {
+        let version = self.take(1)?[0];
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let buckets = self.read_usize()?;
+        let size = self.read_usize()?;
+        let capacity = self.read_usize()?;
+        let n_hashers = self.read_u32()? as usize;
+        let strategy = match self.take(1)?[0] {
+            0 => IndexStrategy::Modulo,
+            1 => IndexStrategy::MultiplyShift,
+            2 => IndexStrategy::DoubleHash,
+            3 => IndexStrategy::BitMask,
+            _ => return Err(DecodeError::Corrupt),
+        };
+        let design_fp_rate = f32::from_bits(self.read_u32()?);
+
+        let mut states = [SipState::with_keys(0, 0); 2];
+        for state in &mut states {
+            let k0 = self.read_u64()?;
+            let k1 = self.read_u64()?;
+            *state = SipState::with_keys(k0, k1);
+        }
+
+        let salted = self.take(1)?[0];
+        let salt = self.read_u64()?;
+        let salt = match salted {
+            0 if salt == 0 => None,
+            1 => Some(salt),
+            _ => return Err(DecodeError::Corrupt),
+        };
+
+        if buckets == 0 || n_hashers == 0 {
+            return Err(DecodeError::Corrupt);
+        }
+        if n_hashers > MAX_HASHERS {
+            return Err(DecodeError::TooLarge);
+        }
+
+        if strategy == IndexStrategy::BitMask && !buckets.is_power_of_two() {
+            return Err(DecodeError::Corrupt);
+        }
+
+        Ok(Header {
+            buckets,
+            size,
+            capacity,
+            n_hashers,
+            strategy,
+            design_fp_rate,
+            states,
+            salt,
+        })
+    }
+
+    /// Read the rest of a dense encoding
+    fn read_dense(mut self) -> Result<BloomFilter, DecodeError> {
+        let header = self.read_header()?;
+
+        let n_bytes = n_bytes(header.buckets);
+        if self.data.len() < n_bytes {
+            return Err(DecodeError::Truncated);
+        }
+        if self.data.len() > n_bytes {
+            return Err(DecodeError::Corrupt);
+        }
+
+        let mut buffer = BitVec::from_bytes(self.data);
+        buffer.truncate(header.buckets);
+
+        Ok(header.into_filter(buffer))
+    }
+
+    /// Read the rest of a sparse encoding
+    ///
+    /// # Arguments
+    /// * `max_buckets`: most buckets the filter may have
+    fn read_sparse(
+        mut self,
+        max_buckets: usize,
+    ) -> Result<BloomFilter, DecodeError> {
+        let header = self.read_header()?;
+        if header.buckets > max_buckets {
+            return Err(DecodeError::TooLarge);
+        }
+        let buckets = header.buckets as u64;
+
+        let n_set = self.read_varint()?;
+        if n_set > buckets {
+            return Err(DecodeError::Corrupt);
+        }
+        // every gap takes at least one byte
+        if n_set > self.data.len() as u64 {
+            return Err(DecodeError::Truncated);
+        }
+
+        let mut bytes = zeroed_bytes(n_bytes(header.buckets))?;
+
+        let mut idx = 0u64;
+        for i in 0..n_set {
+            let gap = self.read_varint()?;
+            if i > 0 && gap == 0 {
+                return Err(DecodeError::Corrupt);
+            }
+
+            idx = idx.checked_add(gap).ok_or(DecodeError::Corrupt)?;
+            if idx >= buckets {
+                return Err(DecodeError::Corrupt);
+            }
+            bytes[idx as usize / 8] |= 0x80 >> (idx % 8);
+        }
+
+        if !self.data.is_empty() {
+            return Err(DecodeError::Corrupt);
+        }
+
+        let mut buffer = BitVec::from_bytes(&bytes);
+        buffer.truncate(header.buckets);
+
+        Ok(header.into_filter(buffer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut filter = BloomFilter::new_with_fp(10, 0.01);
+        for e in 0..10 {
+            filter.insert(&e);
+        }
+
+        let bytes = filter.to_bytes();
+        assert_eq!(HEADER_LEN + n_bytes(filter.buckets()), bytes.len());
+
+        let decoded = BloomFilter::from_bytes(&bytes).unwrap();
+
+        assert_eq!(filter.size(), decoded.size());
+        assert_eq!(filter.buckets(), decoded.buckets());
+        assert_eq!(filter.n_hashers(), decoded.n_hashers());
+        assert_eq!(filter.design_fp_rate(), decoded.design_fp_rate());
+        for e in 0..100 {
+            assert_eq!(filter.may_contain(&e), decoded.may_contain(&e));
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut filter = BloomFilter::new_with_fp(10, 0.01);
+        filter.insert(&"do add this");
+        let bytes = filter.to_bytes();
+
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Err(DecodeError::Truncated),
+                BloomFilter::from_bytes(&bytes[..len]).map(|_| ())
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip_strategy() {
+        let filter =
+            BloomFilter::new_with_strategy(10, 0.01, IndexStrategy::DoubleHash);
+        let decoded = BloomFilter::from_bytes(&filter.to_bytes()).unwrap();
+
+        assert_eq!(IndexStrategy::DoubleHash, decoded.index_strategy());
+        assert_eq!(filter, decoded);
+    }
+
+    #[test]
+    fn test_round_trip_salt() {
+        let mut filter = BloomFilter::new_with_salt(10, 0.01, 42);
+        filter.insert(&"do add this");
+
+        let decoded = BloomFilter::from_bytes(&filter.to_bytes()).unwrap();
+        assert_eq!(Some(42), decoded.salt());
+        assert!(decoded.may_contain(&"do add this"));
+        assert_eq!(filter, decoded);
+    }
+
+    #[test]
+    fn test_sparse_round_trip() {
+        let mut filter = BloomFilter::new_with_size(10_000, 100_000);
+        filter.insert(&"do add this");
+
+        let bytes = filter.to_sparse_bytes();
+        assert_eq!(SPARSE_MAGIC, &bytes[..4]);
+        assert!(bytes.len() < filter.to_bytes().len() / 100);
+
+        let decoded = BloomFilter::from_sparse_bytes(&bytes).unwrap();
+        assert_eq!(filter, decoded);
+        assert_eq!(filter.size(), decoded.size());
+        assert_eq!(filter.design_fp_rate(), decoded.design_fp_rate());
+    }
+
+    #[test]
+    fn test_sparse_falls_back_to_dense() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        for e in 0..100 {
+            filter.insert(&e);
+        }
+
+        let bytes = filter.to_sparse_bytes();
+        assert_eq!(filter.to_bytes(), bytes);
+        assert_eq!(filter, BloomFilter::from_sparse_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_sparse_truncated() {
+        let mut filter = BloomFilter::new_with_size(10_000, 100_000);
+        filter.insert(&"do add this");
+        let bytes = filter.to_sparse_bytes();
+
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Err(DecodeError::Truncated),
+                BloomFilter::from_sparse_bytes(&bytes[..len]).map(|_| ())
+            );
+        }
+    }
+
+    #[test]
+    fn test_sparse_corrupt() {
+        let filter = BloomFilter::new_with_size(10, 100);
+        let header = filter.header(SPARSE_MAGIC);
+
+        // index past the last bucket
+        let mut bytes = header.clone();
+        bytes.extend_from_slice(&[1, 100]);
+        assert_eq!(
+            Err(DecodeError::Corrupt),
+            BloomFilter::from_sparse_bytes(&bytes).map(|_| ())
+        );
+
+        // the same index twice
+        let mut bytes = header.clone();
+        bytes.extend_from_slice(&[2, 5, 0]);
+        assert_eq!(
+            Err(DecodeError::Corrupt),
+            BloomFilter::from_sparse_bytes(&bytes).map(|_| ())
+        );
+
+        // trailing bytes
+        let mut bytes = header;
+        bytes.extend_from_slice(&[1, 5, 0]);
+        assert_eq!(
+            Err(DecodeError::Corrupt),
+            BloomFilter::from_sparse_bytes(&bytes).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_sparse_huge_buckets() {
+        let mut bytes = BloomFilter::new_with_size(10, 100).to_sparse_bytes();
+        assert_eq!(HEADER_LEN + 1, bytes.len());
+        // the bucket count follows the magic and version
+        bytes[5..13].copy_from_slice(&(1u64 << 36).to_le_bytes());
+
+        assert_eq!(
+            Err(DecodeError::TooLarge),
+            BloomFilter::from_sparse_bytes(&bytes).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_sparse_bucket_limit() {
+        let filter = BloomFilter::new_with_size(10, 1000);
+        let bytes = filter.to_sparse_bytes();
+
+        assert_eq!(
+            Err(DecodeError::TooLarge),
+            BloomFilter::from_sparse_bytes_with_limit(&bytes, 999).map(|_| ())
+        );
+        assert_eq!(
+            Ok(filter),
+            BloomFilter::from_sparse_bytes_with_limit(&bytes, 1000)
+        );
+    }
+
+    #[test]
+    fn test_too_many_hashers() {
+        let mut bytes = BloomFilter::new_with_fp(10, 0.01).to_bytes();
+        bytes[29..33].copy_from_slice(&(MAX_HASHERS as u32 + 1).to_le_bytes());
+
+        assert_eq!(
+            Err(DecodeError::TooLarge),
+            BloomFilter::from_bytes(&bytes).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_sparse_count_past_end() {
+        let filter = BloomFilter::new_with_size(10, 100);
+        let mut bytes = filter.header(SPARSE_MAGIC);
+        bytes.extend_from_slice(&[3, 5, 1]);
+
+        assert_eq!(
+            Err(DecodeError::Truncated),
+            BloomFilter::from_sparse_bytes(&bytes).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for &n in &[0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, n);
+
+            let mut reader = Reader { data: &bytes };
+            assert_eq!(Ok(n), reader.read_varint());
+            assert!(reader.data.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_no_hashers() {
+        let mut bytes = BloomFilter::new_with_fp(10, 0.01).to_bytes();
+        // the hasher count follows the magic, version, buckets, size and
+        // capacity
+        for byte in &mut bytes[29..33] {
+            *byte = 0;
+        }
+
+        assert_eq!(
+            Err(DecodeError::Corrupt),
+            BloomFilter::from_bytes(&bytes).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut bytes = BloomFilter::new_with_fp(10, 0.01).to_bytes();
+        bytes[0] = b'X';
+
+        assert_eq!(
+            Err(DecodeError::BadMagic),
+            BloomFilter::from_bytes(&bytes).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut bytes = BloomFilter::new_with_fp(10, 0.01).to_bytes();
+        bytes[4] = VERSION + 1;
+
+        assert_eq!(
+            Err(DecodeError::UnsupportedVersion(VERSION + 1)),
+            BloomFilter::from_bytes(&bytes).map(|_| ())
+        );
+    }
+}
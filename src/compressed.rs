@@ -0,0 +1,248 @@
+//! A compact, query-less form of a `BloomFilter` for storage
+
+use alloc::vec::Vec;
+use core::hash::BuildHasher;
+use core::mem;
+
+use bit_vec::BitVec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::codec::{n_bytes, write_varint, zeroed_bytes, Reader};
+use super::{BloomFilter, DecodeError, Hashers, SipState};
+
+/// Bloom Filter with its buffer run-length encoded
+///
+/// Filters that are mostly empty or mostly full take far less memory this
+/// way, at the cost of not being queryable: `decompress` must restore the
+/// `BloomFilter` before members can be added or looked up. Filters that are
+/// neither keep their buffer packed as is, so compressing never grows one.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompressedBloomFilter<S = SipState> {
+    buffer: Buffer,
+    buckets: usize,
+    size: usize,
+    capacity: usize,
+    design_fp_rate: f32,
+    hashers: Hashers<S>,
+}
+
+/// The encoded buffer of a `CompressedBloomFilter`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum Buffer {
+    /// Lengths of the alternating runs of unset and set buckets, starting
+    /// with unset, as LEB128 varints
+    Runs(Vec<u8>),
+    /// The buckets packed eight to a byte, as `BitVec::to_bytes` does
+    Packed(Vec<u8>),
+}
+
+impl<S> BloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    /// Compress the filter for storage
+    ///
+    /// The buffer is encoded as the lengths of its alternating runs of unset
+    /// and set buckets, unless that would take more space than the buffer
+    /// itself, in which case it's kept as packed bytes.
+    pub fn compress(&self) -> CompressedBloomFilter<S> {
+        let packed_len = n_bytes(self.buckets());
+
+        let mut runs = Vec::new();
+        let mut run_value = false;
+        let mut run_len = 0u64;
+        for bit in self.buffer.iter() {
+            if bit != run_value {
+                write_varint(&mut runs, run_len);
+                run_value = bit;
+                run_len = 0;
+
+                if runs.len() >= packed_len {
+                    break;
+                }
+            }
+            run_len += 1;
+        }
+        write_varint(&mut runs, run_len);
+
+        let buffer = if runs.len() < packed_len {
+            runs.shrink_to_fit();
+            Buffer::Runs(runs)
+        } else {
+            Buffer::Packed(self.buffer.to_bytes())
+        };
+
+        CompressedBloomFilter {
+            buffer,
+            buckets: self.buckets(),
+            size: self.size,
+            capacity: self.capacity,
+            design_fp_rate: self.design_fp_rate,
+            hashers: self.hashers.clone(),
+        }
+    }
+}
+
+impl<S> CompressedBloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    /// Restore the filter that was compressed
+    ///
+    /// Fails if the compressed filter is inconsistent, which can only happen
+    /// if it was deserialized from corrupt data.
+    pub fn decompress(&self) -> Result<BloomFilter<S>, DecodeError> {
+        if self.buckets == 0 {
+            return Err(DecodeError::Corrupt);
+        }
+
+        let mut buffer = match self.buffer {
+            Buffer::Runs(ref runs) => {
+                BitVec::from_bytes(&decode_runs(runs, self.buckets)?)
+            }
+            Buffer::Packed(ref bytes) => {
+                if bytes.len() != n_bytes(self.buckets) {
+                    return Err(DecodeError::Corrupt);
+                }
+                BitVec::from_bytes(bytes)
+            }
+        };
+        buffer.truncate(self.buckets);
+
+        Ok(BloomFilter {
+            buffer,
+            size: self.size,
+            capacity: self.capacity,
+            design_fp_rate: self.design_fp_rate,
+            hashers: self.hashers.clone(),
+            grow_log: None,
+        })
+    }
+
+    /// Number of buckets of the filter that was compressed
+    pub fn buckets(&self) -> usize {
+        self.buckets
+    }
+
+    /// Number of bytes of memory the compressed filter uses
+    ///
+    /// The size of the compressed filter itself, including its hashers, plus
+    /// the encoded buffer it owns.
+    pub fn memory_bytes(&self) -> usize {
+        let bytes = match self.buffer {
+            Buffer::Runs(ref bytes) | Buffer::Packed(ref bytes) => bytes,
+        };
+
+        mem::size_of::<CompressedBloomFilter<S>>() + bytes.capacity()
+    }
+}
+
+/// Pack the buckets described by runs from `BloomFilter::compress` into bytes
+///
+/// # Arguments
+/// * `runs`: encoded runs
+/// * `buckets`: number of buckets the runs must add up to
+fn decode_runs(runs: &[u8], buckets: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut bytes = zeroed_bytes(n_bytes(buckets))?;
+    let mut reader = Reader::new(runs);
+    let mut run_value = false;
+    let mut idx = 0;
+    while idx < buckets {
+        let run_len = reader.read_varint()?;
+        if run_len > (buckets - idx) as u64 {
+            return Err(DecodeError::Corrupt);
+        }
+
+        let end = idx + run_len as usize;
+        if run_value {
+            for i in idx..end {
+                bytes[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+        idx = end;
+        run_value = !run_value;
+    }
+
+    if !reader.is_empty() {
+        return Err(DecodeError::Corrupt);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_round_trips(filter: &BloomFilter) {
+        let compressed = filter.compress();
+        let decompressed = compressed.decompress().unwrap();
+
+        assert_eq!(filter.buckets(), compressed.buckets());
+        assert_eq!(filter, &decompressed);
+        assert_eq!(filter.size(), decompressed.size());
+        assert_eq!(filter.design_fp_rate(), decompressed.design_fp_rate());
+    }
+
+    #[test]
+    fn test_round_trip_sparse() {
+        let mut filter = BloomFilter::new_with_fp(100_000, 0.01);
+        filter.extend(0..100);
+
+        assert_round_trips(&filter);
+        let compressed = filter.compress();
+        assert!(compressed.memory_bytes() < filter.memory_bytes() / 10);
+        let decompressed = compressed.decompress().unwrap();
+        assert!((0..100).all(|e| decompressed.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_round_trip_dense() {
+        let mut filter = BloomFilter::new_with_fp(1000, 0.01);
+        filter.extend(0..1000);
+
+        assert_round_trips(&filter);
+        let compressed = filter.compress();
+        assert!(compressed.memory_bytes() <= filter.memory_bytes());
+        let decompressed = compressed.decompress().unwrap();
+        assert!((0..1000).all(|e| decompressed.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_round_trip_empty_and_full() {
+        let mut filter = BloomFilter::new_with_size(10, 1000);
+        assert_round_trips(&filter);
+
+        filter.buffer.set_all();
+        assert_round_trips(&filter);
+    }
+
+    #[test]
+    fn test_decompress_corrupt() {
+        let mut filter = BloomFilter::new_with_size(10, 1000);
+        filter.extend(0..10);
+        let compressed = filter.compress();
+
+        // runs that overshoot the buckets
+        let mut corrupt = compressed.clone();
+        corrupt.buffer = Buffer::Runs(vec![0xe9, 0x07]);
+        assert_eq!(Err(DecodeError::Corrupt), corrupt.decompress().map(|_| ()));
+
+        // runs that stop short of the buckets
+        let mut corrupt = compressed.clone();
+        corrupt.buffer = Buffer::Runs(vec![10]);
+        assert_eq!(
+            Err(DecodeError::Truncated),
+            corrupt.decompress().map(|_| ())
+        );
+
+        // packed bytes of the wrong length
+        let mut corrupt = compressed;
+        corrupt.buffer = Buffer::Packed(vec![0; 10]);
+        assert_eq!(Err(DecodeError::Corrupt), corrupt.decompress().map(|_| ()));
+    }
+}
@@ -0,0 +1,199 @@
+//! Approximate frequency counts using the same hashing as the filters
+
+use alloc::vec::Vec;
+use core::f32::consts::E;
+use core::hash::Hash;
+
+use super::{check_fp_rate, math};
+use super::{Hashers, SipState};
+
+/// Space-efficient approximate counter of how often elements occur
+///
+/// Counters are laid out in `depth` rows of `width`, and each of an element's
+/// indexes falls in its own row. Collisions only ever add to a counter, so
+/// the smallest of an element's counters never underestimates its count.
+#[derive(Debug, Clone)]
+pub struct CountMinSketch {
+    counters: Vec<u32>,
+    width: usize,
+    total: u64,
+    hashers: Hashers<SipState>,
+}
+
+impl CountMinSketch {
+    /// Build a Count-Min Sketch with a specified error bound
+    ///
+    /// With probability `1 - delta`, an estimate exceeds the true count by at
+    /// most `epsilon` times the total of all counts.
+    ///
+    /// # Arguments
+    /// * `epsilon`: overestimate as a fraction of the total (0.0 -> 1.0)
+    /// * `delta`: probability of exceeding that overestimate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `epsilon` or `delta` isn't strictly between 0.0 and 1.0
+    pub fn new_with_error(epsilon: f32, delta: f32) -> CountMinSketch {
+        for &rate in &[epsilon, delta] {
+            if let Err(e) = check_fp_rate(rate) {
+                panic!("{}", e);
+            }
+        }
+
+        let width = math::ceil(E / epsilon) as usize;
+        let depth = math::ceil(math::ln(1. / delta)) as usize;
+
+        CountMinSketch::new_with_size(width, depth)
+    }
+
+    /// Create a new Count-Min Sketch with specified dimensions
+    ///
+    /// # Arguments
+    /// * `width`: number of counters in each row, rounded up to 1 if zero
+    /// * `depth`: number of rows, rounded up to 1 if zero
+    pub fn new_with_size(width: usize, depth: usize) -> CountMinSketch {
+        let width = width.max(1);
+        let depth = depth.max(1);
+
+        CountMinSketch {
+            counters: vec![0; width * depth],
+            width,
+            total: 0,
+            hashers: Hashers::new(depth, SipState::new),
+        }
+    }
+
+    /// Count an occurrence of an element
+    ///
+    /// Counters saturate at `u32::MAX` instead of overflowing.
+    ///
+    /// # Arguments
+    /// * `e`: element to count
+    pub fn insert<T>(&mut self, e: &T)
+    where
+        T: Hash,
+    {
+        for idx in self.indexes(e) {
+            self.counters[idx] = self.counters[idx].saturating_add(1);
+        }
+
+        self.total += 1;
+    }
+
+    /// Estimate how many times an element was counted
+    ///
+    /// Never less than the true count.
+    ///
+    /// # Arguments
+    /// * `e`: element to estimate the count of
+    pub fn estimate<T>(&self, e: &T) -> u32
+    where
+        T: Hash,
+    {
+        self.indexes(e).map(|idx| self.counters[idx]).min().unwrap()
+    }
+
+    /// Reset every count to zero
+    pub fn clear(&mut self) {
+        for counter in &mut self.counters {
+            *counter = 0;
+        }
+
+        self.total = 0;
+    }
+
+    /// Total number of occurrences counted
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Number of counters in each row
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows, which is also the number of hashers
+    pub fn depth(&self) -> usize {
+        self.hashers.len()
+    }
+
+    /// The counters that an element hashes to, the `i`th within row `i`
+    fn indexes<T>(&self, e: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        let width = self.width;
+
+        self.hashers
+            .indexes(e, width)
+            .enumerate()
+            .map(move |(i, idx)| i * width + idx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Number of times `skewed_sketch` counts element `e`
+    fn true_count(e: u32) -> u32 {
+        e % 100 + 1
+    }
+
+    /// Count each of 1000 elements a different number of times
+    fn skewed_sketch(sketch: &mut CountMinSketch) {
+        for e in 0..1000 {
+            for _ in 0..true_count(e) {
+                sketch.insert(&e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_never_underestimates() {
+        let mut sketch = CountMinSketch::new_with_size(100, 4);
+        skewed_sketch(&mut sketch);
+
+        assert!((0..1000).all(|e| sketch.estimate(&e) >= true_count(e)));
+    }
+
+    #[test]
+    fn test_within_error_bound() {
+        let mut sketch = CountMinSketch::new_with_error(0.001, 0.01);
+        skewed_sketch(&mut sketch);
+
+        let bound = (0.001 * sketch.total() as f32) as u32;
+        let exceeding = (0..1000)
+            .filter(|&e| sketch.estimate(&e) > true_count(e) + bound)
+            .count();
+
+        // each estimate exceeds the bound with probability at most 1%
+        assert!(exceeding < 50);
+    }
+
+    #[test]
+    fn test_absent_is_zero() {
+        let mut sketch = CountMinSketch::new_with_error(0.001, 0.01);
+        sketch.insert(&"do add this");
+
+        assert_eq!(1, sketch.estimate(&"do add this"));
+        assert_eq!(0, sketch.estimate(&"don't add this"));
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let sketch = CountMinSketch::new_with_error(0.01, 0.01);
+
+        assert_eq!(272, sketch.width());
+        assert_eq!(5, sketch.depth());
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut sketch = CountMinSketch::new_with_size(100, 4);
+        skewed_sketch(&mut sketch);
+        sketch.clear();
+
+        assert_eq!(0, sketch.total());
+        assert!((0..1000u32).all(|e| sketch.estimate(&e) == 0));
+    }
+}
@@ -0,0 +1,579 @@
+//! A Bloom Filter variant that supports removal
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use bit_vec::BitVec;
+
+use super::{
+    check_fp_rate, false_positive_rate, min_n_buckets, optimal_n_hashers,
+};
+use super::{BloomFilter, Hashers, SipState};
+
+/// Number of bits in each counter of a `CountingBloomFilter`
+///
+/// Narrower counters take less memory but saturate sooner under heavy churn.
+/// 4 bits is the usual choice, since a counter rarely exceeds 15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CounterWidth {
+    /// Counters saturate at 15, two are packed into each byte
+    Bits4,
+    /// Counters saturate at `u8::MAX`, the default
+    #[default]
+    Bits8,
+    /// Counters saturate at `u16::MAX`
+    Bits16,
+}
+
+impl CounterWidth {
+    /// Largest count a counter can hold, at which it saturates
+    pub fn max_count(self) -> u16 {
+        match self {
+            CounterWidth::Bits4 => 0xf,
+            CounterWidth::Bits8 => u16::from(u8::MAX),
+            CounterWidth::Bits16 => u16::MAX,
+        }
+    }
+
+    /// Number of bytes needed to hold a number of counters
+    fn n_bytes(self, n_counters: usize) -> usize {
+        match self {
+            CounterWidth::Bits4 => n_counters.div_ceil(2),
+            CounterWidth::Bits8 => n_counters,
+            CounterWidth::Bits16 => n_counters * 2,
+        }
+    }
+}
+
+/// Space-efficient probabilistic hash set that supports removal
+///
+/// Every bucket holds a saturating counter instead of a single bit, as wide
+/// as its `CounterWidth`. A counter that reaches the maximum is pinned there:
+/// its true count is no longer known, so it is never decremented, which
+/// keeps removals from introducing false negatives for the other members
+/// sharing that bucket.
+#[derive(Debug)]
+pub struct CountingBloomFilter {
+    counters: Vec<u8>,
+    n_counters: usize,
+    width: CounterWidth,
+    size: usize,
+    capacity: usize,
+    design_fp_rate: f32,
+    hashers: Hashers<SipState>,
+}
+
+impl CountingBloomFilter {
+    /// Build a Counting Bloom Filter with a specified false positive rate
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_fp(n_elems: usize, fp_rate: f32) -> CountingBloomFilter {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        let mut filter = CountingBloomFilter::new_with_size(
+            n_elems,
+            min_n_buckets(n_elems, fp_rate),
+        );
+        filter.design_fp_rate = fp_rate;
+        filter
+    }
+
+    /// Create a new Counting Bloom Filter with specified number of counters
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `size`: desired number of counters, rounded up to 1 if zero
+    pub fn new_with_size(n_elems: usize, size: usize) -> CountingBloomFilter {
+        CountingBloomFilter::new_with_size_and_width(
+            n_elems,
+            size,
+            CounterWidth::default(),
+        )
+    }
+
+    /// Create a new Counting Bloom Filter with specified number and width of
+    /// counters
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `size`: desired number of counters, rounded up to 1 if zero
+    /// * `width`: number of bits in each counter
+    pub fn new_with_size_and_width(
+        n_elems: usize,
+        size: usize,
+        width: CounterWidth,
+    ) -> CountingBloomFilter {
+        let size = size.max(1);
+        let n_hashers = optimal_n_hashers(size, n_elems);
+
+        CountingBloomFilter {
+            counters: vec![0; width.n_bytes(size)],
+            n_counters: size,
+            width,
+            size: 0,
+            capacity: n_elems,
+            design_fp_rate: false_positive_rate(size, n_hashers, n_elems),
+            hashers: Hashers::new(n_hashers, SipState::new),
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T)
+    where
+        T: Hash,
+    {
+        let max_count = self.width.max_count();
+        for idx in self.indexes(e) {
+            let count = self.counter(idx);
+            if count != max_count {
+                self.set_counter(idx, count + 1);
+            }
+        }
+
+        self.size += 1;
+    }
+
+    /// Insert a member a number of times at once
+    ///
+    /// The same as calling `insert` `n` times, but each counter is only
+    /// updated once, saturating at the counter width's `max_count`.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    /// * `n`: number of times to add it
+    pub fn insert_n<T>(&mut self, e: &T, n: u32)
+    where
+        T: Hash,
+    {
+        let max_count = u32::from(self.width.max_count());
+        for idx in self.indexes(e) {
+            let count = u32::from(self.counter(idx));
+            self.set_counter(
+                idx,
+                count.saturating_add(n).min(max_count) as u16,
+            );
+        }
+
+        self.size = self.size.saturating_add(n as usize);
+    }
+
+    /// Remove a member
+    ///
+    /// Returns whether the element was possibly a member. Nothing is changed
+    /// if it definitely was not.
+    ///
+    /// # Arguments
+    /// * `e`: element to remove
+    pub fn remove<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        if !self.may_contain(e) {
+            return false;
+        }
+
+        let max_count = self.width.max_count();
+        for idx in self.indexes(e) {
+            let count = self.counter(idx);
+            if count != max_count {
+                self.set_counter(idx, count - 1);
+            }
+        }
+
+        self.size = self.size.saturating_sub(1);
+        true
+    }
+
+    /// Remove every member yielded by an iterator
+    ///
+    /// Returns how many were possibly members, see `remove`. Elements that
+    /// definitely weren't are skipped, so no counter drops below zero.
+    ///
+    /// # Arguments
+    /// * `elems`: elements to remove
+    pub fn remove_all<T, I>(&mut self, elems: I) -> usize
+    where
+        T: Hash,
+        I: IntoIterator<Item = T>,
+    {
+        elems.into_iter().filter(|e| self.remove(e)).count()
+    }
+
+    /// Halve every counter, rounding down, to age out old inserts
+    ///
+    /// Called periodically, this weights recent inserts over older ones for
+    /// approximate counts over a moving window. A member whose counters fall
+    /// to zero is no longer reported, so a member inserted only once is gone
+    /// after a single decay, and saturated counters are halved like any
+    /// other. The size is halved too, as an approximation.
+    pub fn decay(&mut self) {
+        for idx in 0..self.n_counters {
+            let count = self.counter(idx);
+            self.set_counter(idx, count >> 1);
+        }
+
+        self.size /= 2;
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.indexes(e).all(|idx| self.counter(idx) > 0)
+    }
+
+    /// Estimate how many times a member was inserted
+    ///
+    /// The smallest of the member's counters. Other members sharing its
+    /// buckets can only raise them, so this never underestimates the count
+    /// of a member that wasn't removed, unless its counters saturated at the
+    /// counter width's `max_count`.
+    ///
+    /// # Arguments
+    /// * `e`: element to estimate the count of
+    pub fn estimate<T>(&self, e: &T) -> u16
+    where
+        T: Hash,
+    {
+        self.indexes(e)
+            .map(|idx| self.counter(idx))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Number of elements in the `CountingBloomFilter`
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether no elements are in the `CountingBloomFilter`
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Number of buckets that a member can occupy
+    pub fn buckets(&self) -> usize {
+        self.n_counters
+    }
+
+    /// Number of bits in each counter
+    pub fn counter_width(&self) -> CounterWidth {
+        self.width
+    }
+
+    /// How many counters hold each count
+    ///
+    /// Entry `i` is the number of counters equal to `i`, up to the counter
+    /// width's `max_count`. Many counters at the maximum mean the filter is
+    /// saturating and removals are being ignored.
+    pub fn counter_histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![0; usize::from(self.width.max_count()) + 1];
+        for idx in 0..self.n_counters {
+            histogram[usize::from(self.counter(idx))] += 1;
+        }
+        histogram
+    }
+
+    /// A standard Bloom Filter holding the same members
+    ///
+    /// A bucket is set wherever a counter is nonzero, and the hashers are
+    /// kept, so every member still present here is reported present there.
+    /// It is designed for the same number of elements and false positive
+    /// rate. The result takes a bit per bucket, but can no longer remove
+    /// members.
+    pub fn to_bloom_filter(&self) -> BloomFilter {
+        let mut buffer = BitVec::from_elem(self.n_counters, false);
+        for idx in (0..self.n_counters).filter(|&idx| self.counter(idx) > 0) {
+            buffer.set(idx, true);
+        }
+
+        let mut filter = BloomFilter::from_parts(
+            buffer,
+            self.size,
+            *self.hashers.states(),
+            self.n_hashers(),
+        )
+        .expect("counting filters have buckets and hashers");
+        filter.hashers = self.hashers.clone();
+        filter.capacity = self.capacity;
+        filter.design_fp_rate = self.design_fp_rate;
+        filter
+    }
+
+    /// Number of hashers being used
+    pub fn n_hashers(&self) -> usize {
+        self.hashers.len()
+    }
+
+    /// False positive rate
+    pub fn fp_rate(&self) -> f32 {
+        false_positive_rate(self.buckets(), self.n_hashers(), self.size())
+    }
+
+    /// The indexes that a element hashes to
+    fn indexes<T>(&self, e: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        self.hashers.indexes(e, self.n_counters)
+    }
+
+    /// The count in a bucket
+    fn counter(&self, idx: usize) -> u16 {
+        match self.width {
+            CounterWidth::Bits4 => {
+                u16::from(self.counters[idx / 2] >> (idx % 2 * 4) & 0xf)
+            }
+            CounterWidth::Bits8 => u16::from(self.counters[idx]),
+            CounterWidth::Bits16 => u16::from_le_bytes([
+                self.counters[idx * 2],
+                self.counters[idx * 2 + 1],
+            ]),
+        }
+    }
+
+    /// Overwrite the count in a bucket, which must fit the counter width
+    fn set_counter(&mut self, idx: usize, count: u16) {
+        match self.width {
+            CounterWidth::Bits4 => {
+                let shift = idx % 2 * 4;
+                let byte = &mut self.counters[idx / 2];
+                *byte = *byte & !(0xf << shift) | (count as u8) << shift;
+            }
+            CounterWidth::Bits8 => self.counters[idx] = count as u8,
+            CounterWidth::Bits16 => {
+                let [lo, hi] = count.to_le_bytes();
+                self.counters[idx * 2] = lo;
+                self.counters[idx * 2 + 1] = hi;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_fp_out_of_range() {
+        CountingBloomFilter::new_with_fp(10, 1.5);
+    }
+
+    #[test]
+    fn test_remove() {
+        let to_add = "do add this";
+
+        let mut filter = CountingBloomFilter::new_with_size(1, 100);
+        filter.insert(&to_add);
+        assert!(filter.may_contain(&to_add));
+
+        assert!(filter.remove(&to_add));
+        assert!(!filter.may_contain(&to_add));
+        assert_eq!(0, filter.size());
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn test_remove_absent_is_noop() {
+        let mut filter = CountingBloomFilter::new_with_size(1, 100);
+        filter.insert(&"do add this");
+
+        assert!(!filter.remove(&"dont add this"));
+        assert_eq!(1, filter.size());
+        assert!(filter.may_contain(&"do add this"));
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let mut filter = CountingBloomFilter::new_with_fp(100, 0.01);
+        for e in 0..100 {
+            filter.insert(&e);
+        }
+
+        assert_eq!(50, filter.remove_all(0..50));
+        assert_eq!(50, filter.size());
+        assert!((50..100).all(|e| filter.may_contain(&e)));
+
+        let still_present = (0..50).filter(|e| filter.may_contain(e)).count();
+        assert!(still_present < 5);
+    }
+
+    /// Test that removing elements from saturated counters can't cause a false
+    /// negative for an element that was never removed
+    #[test]
+    fn test_saturated_counters_are_not_decremented() {
+        let to_add = "do add this";
+
+        let mut filter = CountingBloomFilter::new_with_size(1, 100);
+        for _ in 0..300 {
+            filter.insert(&to_add);
+        }
+
+        let idxs: Vec<_> = filter.indexes(&to_add).collect();
+        assert!(idxs.iter().all(|&idx| filter.counter(idx) == 255));
+
+        for _ in 0..300 {
+            assert!(filter.remove(&to_add));
+        }
+
+        assert!(idxs.iter().all(|&idx| filter.counter(idx) == 255));
+        assert!(filter.may_contain(&to_add));
+    }
+
+    #[test]
+    fn test_counter_histogram() {
+        let mut filter = CountingBloomFilter::new_with_size_and_width(
+            10,
+            1000,
+            CounterWidth::Bits4,
+        );
+        for _ in 0..20 {
+            filter.insert(&"hot");
+        }
+        filter.insert(&"cold");
+
+        let mut hot: Vec<_> = filter.indexes(&"hot").collect();
+        hot.sort();
+        hot.dedup();
+
+        let histogram = filter.counter_histogram();
+        assert_eq!(16, histogram.len());
+        assert_eq!(1000, histogram.iter().sum::<usize>());
+        assert_eq!(hot.len(), histogram[15]);
+        assert!(histogram[1] > 0);
+        assert!(histogram[0] >= 1000 - 2 * filter.n_hashers());
+    }
+
+    const WIDTHS: [CounterWidth; 3] = [
+        CounterWidth::Bits4,
+        CounterWidth::Bits8,
+        CounterWidth::Bits16,
+    ];
+
+    #[test]
+    fn test_widths_saturate() {
+        for &width in &WIDTHS {
+            let max_count = width.max_count();
+            let mut filter =
+                CountingBloomFilter::new_with_size_and_width(1, 100, width);
+            assert_eq!(width, filter.counter_width());
+
+            for _ in 0..u32::from(max_count) + 10 {
+                filter.insert(&"do add this");
+            }
+            let idxs: Vec<_> = filter.indexes(&"do add this").collect();
+            assert!(idxs.iter().all(|&idx| filter.counter(idx) == max_count));
+
+            assert!(filter.remove(&"do add this"));
+            assert!(idxs.iter().all(|&idx| filter.counter(idx) == max_count));
+
+            // neighbouring counters are untouched
+            let others = (0..100).filter(|idx| !idxs.contains(idx));
+            assert!(others.map(|idx| filter.counter(idx)).all(|c| c == 0));
+        }
+    }
+
+    #[test]
+    fn test_insert_n() {
+        let mut filter = CountingBloomFilter::new_with_fp(100, 0.01);
+        filter.insert_n(&"weighted", 5);
+        filter.insert(&"other");
+
+        assert!(filter.estimate(&"weighted") >= 5);
+        assert_eq!(6, filter.size());
+
+        for _ in 0..5 {
+            assert!(filter.remove(&"weighted"));
+        }
+        assert!(filter.estimate(&"weighted") <= 1);
+    }
+
+    #[test]
+    fn test_insert_n_saturates() {
+        for &width in &WIDTHS {
+            let mut filter =
+                CountingBloomFilter::new_with_size_and_width(1, 100, width);
+            filter.insert_n(&"do add this", u32::MAX);
+            assert_eq!(width.max_count(), filter.estimate(&"do add this"));
+        }
+    }
+
+    #[test]
+    fn test_to_bloom_filter() {
+        let mut counting = CountingBloomFilter::new_with_fp(100, 0.01);
+        for e in 0..100 {
+            counting.insert(&e);
+        }
+        assert_eq!(50, counting.remove_all(50..100));
+
+        let mut filter = counting.to_bloom_filter();
+        assert_eq!(counting.buckets(), filter.buckets());
+        assert_eq!(counting.n_hashers(), filter.n_hashers());
+        assert_eq!(50, filter.size());
+        assert_eq!(100, filter.capacity());
+        assert_eq!(0.01, filter.design_fp_rate());
+        assert!((0..50).all(|e| filter.may_contain(&e)));
+        assert!((50..100).any(|e| !filter.may_contain(&e)));
+
+        let histogram = counting.counter_histogram();
+        assert_eq!(counting.buckets() - histogram[0], filter.set_bits());
+
+        assert_eq!(Ok(true), filter.try_insert(&"do add this"));
+    }
+
+    #[test]
+    fn test_decay() {
+        for &width in &WIDTHS {
+            let mut filter =
+                CountingBloomFilter::new_with_size_and_width(10, 1000, width);
+            filter.insert_n(&"hot", 12);
+            filter.insert(&"cold");
+
+            filter.decay();
+            assert_eq!(6, filter.estimate(&"hot"));
+            assert!(!filter.may_contain(&"cold"));
+            assert_eq!(6, filter.size());
+
+            for _ in 0..20 {
+                filter.decay();
+            }
+            assert!(filter.is_empty());
+            assert_eq!(filter.buckets(), filter.counter_histogram()[0]);
+        }
+    }
+
+    #[test]
+    fn test_widths_remove() {
+        for &width in &WIDTHS {
+            let mut filter =
+                CountingBloomFilter::new_with_size_and_width(10, 1000, width);
+            for e in 0..10 {
+                filter.insert(&e);
+                filter.insert(&e);
+            }
+
+            for e in 0..10 {
+                assert!(filter.remove(&e));
+                assert!(filter.remove(&e));
+            }
+
+            assert!(filter.is_empty());
+            assert!((0..1000).all(|idx| filter.counter(idx) == 0));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! A Bloom Filter that also keeps exact membership, for testing
+
+use core::hash::{BuildHasher, Hash};
+use std::collections::HashSet;
+
+use super::{BloomFilter, FnvState};
+
+/// Bloom Filter that tracks the members actually inserted alongside it
+///
+/// Each member is hashed once, with `FnvState`, and the 64-bit hash is both
+/// inserted with `BloomFilter::insert_hash` and kept in a set. The set makes
+/// it possible to check the filter for false negatives and to measure its
+/// false positive rate, at the cost of memory that grows with every member,
+/// so it is meant for tests rather than production.
+///
+/// Only available with the `std` feature.
+#[derive(Debug, Clone)]
+pub struct DebugBloomFilter {
+    filter: BloomFilter,
+    inserted: HashSet<u64>,
+}
+
+impl DebugBloomFilter {
+    /// Build an empty Debug Bloom Filter with a specified false positive rate
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new(n_elems: usize, fp_rate: f32) -> DebugBloomFilter {
+        DebugBloomFilter {
+            filter: BloomFilter::new_with_fp(n_elems, fp_rate),
+            inserted: HashSet::new(),
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// Returns whether at least one new bit was set, like
+    /// `BloomFilter::insert`.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        let h = FnvState::new().hash_one(e);
+        self.inserted.insert(h);
+        self.filter.insert_hash(h)
+    }
+
+    /// Check membership in the filter
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.filter.may_contain_hash(FnvState::new().hash_one(e))
+    }
+
+    /// Check whether an element was actually inserted
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn was_inserted<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.inserted.contains(&FnvState::new().hash_one(e))
+    }
+
+    /// Assert that the filter reports every inserted member as present
+    ///
+    /// # Panics
+    /// If the filter gives a false negative for any member
+    pub fn assert_no_false_negatives(&self) {
+        for &h in &self.inserted {
+            assert!(
+                self.filter.may_contain_hash(h),
+                "false negative for hash {:#018x}",
+                h
+            );
+        }
+    }
+
+    /// Fraction of queries that weren't inserted but are reported present
+    ///
+    /// Queries that were inserted are ignored. Zero if every query was
+    /// inserted.
+    ///
+    /// # Arguments
+    /// * `queries`: elements to check membership of
+    pub fn observed_fp_rate<T>(&self, queries: &[T]) -> f32
+    where
+        T: Hash,
+    {
+        let mut negatives = 0;
+        let mut false_positives = 0;
+
+        for e in queries {
+            let h = FnvState::new().hash_one(e);
+            if !self.inserted.contains(&h) {
+                negatives += 1;
+                if self.filter.may_contain_hash(h) {
+                    false_positives += 1;
+                }
+            }
+        }
+
+        if negatives == 0 {
+            0.
+        } else {
+            false_positives as f32 / negatives as f32
+        }
+    }
+
+    /// The filter being tracked
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::vec::Vec;
+
+    #[test]
+    fn test_observed_fp_rate() {
+        let mut filter = DebugBloomFilter::new(1000, 0.01);
+        for e in 0..1000 {
+            filter.insert(&e);
+        }
+        filter.assert_no_false_negatives();
+
+        let queries: Vec<u32> = (0..101_000).collect();
+        let observed = filter.observed_fp_rate(&queries);
+        assert!(observed > 0.005 && observed < 0.02, "{}", observed);
+    }
+
+    #[test]
+    fn test_inserted_queries_are_ignored() {
+        let mut filter = DebugBloomFilter::new(10, 0.01);
+        filter.insert(&"present");
+
+        assert!(filter.was_inserted(&"present"));
+        assert!(!filter.was_inserted(&"absent"));
+        assert_eq!(0., filter.observed_fp_rate(&["present"]));
+    }
+}
@@ -0,0 +1,122 @@
+//! Errors returned by fallible filter operations
+
+use core::error::Error;
+use core::fmt;
+
+/// Error returned when a filter can't be built from the given parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// The false positive rate isn't strictly between 0.0 and 1.0
+    InvalidFpRate,
+    /// The filter would have no buckets
+    NoBuckets,
+    /// The filter would have no hashers
+    NoHashers,
+    /// Too few parameters were given to size the filter
+    MissingParameters,
+    /// The given parameters can't all be satisfied at once
+    ConflictingParameters,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::InvalidFpRate => write!(
+                f,
+                "false positive rate must be strictly between 0.0 and 1.0"
+            ),
+            BuildError::NoBuckets => {
+                write!(f, "filter must have at least one bucket")
+            }
+            BuildError::NoHashers => {
+                write!(f, "filter must have at least one hasher")
+            }
+            BuildError::MissingParameters => {
+                write!(f, "not enough parameters to size the filter")
+            }
+            BuildError::ConflictingParameters => write!(
+                f,
+                "filter parameters can't achieve the false positive rate"
+            ),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Error returned when two filters can't be combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeError {
+    /// The filters have a different number of buckets
+    BucketMismatch,
+    /// The filters hash their members differently
+    HasherMismatch,
+    /// The requested bucket count doesn't evenly divide the current one
+    IndivisibleBuckets,
+    /// There were no filters to combine
+    NoFilters,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeError::BucketMismatch => {
+                write!(f, "filters have a different number of buckets")
+            }
+            MergeError::HasherMismatch => {
+                write!(f, "filters use different hashers")
+            }
+            MergeError::IndivisibleBuckets => write!(
+                f,
+                "new bucket count must evenly divide the current bucket count"
+            ),
+            MergeError::NoFilters => write!(f, "no filters to combine"),
+        }
+    }
+}
+
+impl Error for MergeError {}
+
+/// Error returned when a filter already holds as many elements as it was
+/// built for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaturatedError;
+
+impl fmt::Display for SaturatedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "filter is at capacity")
+    }
+}
+
+impl Error for SaturatedError {}
+
+/// Error returned when bytes can't be decoded into a filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data doesn't start with the expected magic bytes
+    BadMagic,
+    /// The data was encoded with an unknown format version
+    UnsupportedVersion(u8),
+    /// The data ended before the encoded filter did
+    Truncated,
+    /// The data is inconsistent with itself
+    Corrupt,
+    /// The encoded filter is larger than the decoder accepts
+    TooLarge,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::BadMagic => write!(f, "not an encoded filter"),
+            DecodeError::UnsupportedVersion(v) => {
+                write!(f, "unsupported encoding version {}", v)
+            }
+            DecodeError::Truncated => write!(f, "encoded filter is truncated"),
+            DecodeError::Corrupt => write!(f, "encoded filter is corrupt"),
+            DecodeError::TooLarge => write!(f, "encoded filter is too large"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
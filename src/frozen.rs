@@ -0,0 +1,128 @@
+//! A read-only `BloomFilter`
+
+use core::hash::{BuildHasher, Hash};
+
+use super::{BloomFilter, SipState};
+
+/// Bloom Filter that can no longer be inserted into
+///
+/// Built with `BloomFilter::freeze`, it only exposes lookups, so a filter can
+/// be handed to readers, e.g. behind an `Arc`, without them being able to
+/// change it.
+///
+/// ```compile_fail
+/// use bloom::BloomFilter;
+///
+/// let mut frozen = BloomFilter::new_with_fp(10, 0.01).freeze();
+/// frozen.insert(&"do add this");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrozenBloomFilter<S = SipState> {
+    filter: BloomFilter<S>,
+}
+
+impl<S> BloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    /// Make the filter read-only
+    pub fn freeze(self) -> FrozenBloomFilter<S> {
+        FrozenBloomFilter { filter: self }
+    }
+}
+
+impl<S> FrozenBloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.filter.may_contain(e)
+    }
+
+    /// Check membership of every element
+    ///
+    /// # Arguments
+    /// * `elems`: elements to check membership of
+    pub fn contains_all<'a, T, I>(&self, elems: I) -> bool
+    where
+        T: Hash + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        self.filter.contains_all(elems)
+    }
+
+    /// Approximate number of distinct elements in the filter
+    pub fn size(&self) -> usize {
+        self.filter.size()
+    }
+
+    /// Whether no elements were inserted before freezing
+    pub fn is_empty(&self) -> bool {
+        self.filter.is_empty()
+    }
+
+    /// Number of buckets that a member can occupy
+    pub fn buckets(&self) -> usize {
+        self.filter.buckets()
+    }
+
+    /// Number of hashers being used
+    pub fn n_hashers(&self) -> usize {
+        self.filter.n_hashers()
+    }
+
+    /// False positive rate
+    pub fn fp_rate(&self) -> f32 {
+        self.filter.fp_rate()
+    }
+
+    /// The frozen filter, for anything else that only reads it
+    pub fn as_filter(&self) -> &BloomFilter<S> {
+        &self.filter
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use std::thread;
+
+    #[test]
+    fn test_freeze() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        filter.extend(0..100);
+        let (size, buckets) = (filter.size(), filter.buckets());
+
+        let frozen = filter.freeze();
+        assert_eq!(size, frozen.size());
+        assert_eq!(buckets, frozen.buckets());
+        assert!((0..100).all(|e| frozen.may_contain(&e)));
+        assert!((100..200).any(|e| !frozen.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_share_between_threads() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        filter.extend(0..100);
+        let frozen = Arc::new(filter.freeze());
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = Arc::clone(&frozen);
+                thread::spawn(move || (0..100).all(|e| frozen.may_contain(&e)))
+            })
+            .collect();
+
+        assert!(readers.into_iter().all(|r| r.join().unwrap()));
+    }
+}
@@ -0,0 +1,781 @@
+//! Hashing members into buckets
+
+use core::hash::BuildHasher;
+use core::hash::Hash;
+use core::hash::Hasher;
+
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+
+use siphasher::sip::SipHasher13;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Builds SipHash-1-3 hashers with a fixed pair of keys
+///
+/// Unlike `RandomState`, the keys can be read back, so a filter hashed with
+/// them can be rebuilt elsewhere and produce the same bit pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SipState {
+    k0: u64,
+    k1: u64,
+}
+
+impl SipState {
+    /// Create a `SipState` with random keys
+    #[cfg(feature = "std")]
+    pub fn new() -> SipState {
+        let random = RandomState::new();
+
+        SipState {
+            k0: random.hash_one(0u64),
+            k1: random.hash_one(1u64),
+        }
+    }
+
+    /// Create a `SipState` with the next keys of a fixed sequence
+    ///
+    /// Without `std` there's no source of randomness. Every call still gets
+    /// different keys, but the sequence is the same in every run.
+    #[cfg(not(feature = "std"))]
+    pub fn new() -> SipState {
+        let n = next_seed();
+
+        SipState {
+            k0: mix(n),
+            k1: mix(!n),
+        }
+    }
+
+    /// Create a `SipState` with the given keys
+    ///
+    /// # Arguments
+    /// * `k0`: first half of the key
+    /// * `k1`: second half of the key
+    pub fn with_keys(k0: u64, k1: u64) -> SipState {
+        SipState { k0, k1 }
+    }
+
+    /// The keys used to build hashers
+    pub fn keys(&self) -> (u64, u64) {
+        (self.k0, self.k1)
+    }
+}
+
+/// A factory for `Hashers::new` that builds a `SipState` from each seed in
+/// turn
+///
+/// # Arguments
+/// * `seeds`: keys for the two underlying hashers
+pub(crate) fn seeded(seeds: [(u64, u64); 2]) -> impl FnMut() -> SipState {
+    let mut next = 0;
+    move || {
+        let (k0, k1) = seeds[next];
+        next += 1;
+        SipState::with_keys(k0, k1)
+    }
+}
+
+impl Default for SipState {
+    fn default() -> SipState {
+        SipState::new()
+    }
+}
+
+impl BuildHasher for SipState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_keys(self.k0, self.k1)
+    }
+}
+
+/// FNV-1a's 64-bit prime
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a's 64-bit offset basis
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Builds FNV-1a hashers that hash the same on every platform
+///
+/// `Hasher` writes integers in native byte order by default, and `usize` in
+/// its native width, so even hashers with fixed keys can disagree between
+/// platforms. These write every integer as little-endian bytes and `usize`
+/// as a `u64`, which together with fixed offset bases makes a member's hash,
+/// and so its indexes, the same everywhere. FNV is much weaker than SipHash,
+/// so these shouldn't be used for untrusted members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FnvState {
+    basis: u64,
+}
+
+impl FnvState {
+    /// Create an `FnvState` with the standard offset basis
+    pub fn new() -> FnvState {
+        FnvState::with_basis(FNV_OFFSET_BASIS)
+    }
+
+    /// Create an `FnvState` starting from the given offset basis
+    ///
+    /// # Arguments
+    /// * `basis`: initial state of every hasher built
+    pub fn with_basis(basis: u64) -> FnvState {
+        FnvState { basis }
+    }
+}
+
+/// Offset bases of the two hashers of a portable filter: the standard one,
+/// and the upper half of FNV-1a's 128-bit offset basis
+pub(crate) const PORTABLE_BASES: [u64; 2] =
+    [FNV_OFFSET_BASIS, 0x6c62_272e_07bb_0142];
+
+impl Default for FnvState {
+    fn default() -> FnvState {
+        FnvState::new()
+    }
+}
+
+impl BuildHasher for FnvState {
+    type Hasher = FnvHasher;
+
+    fn build_hasher(&self) -> FnvHasher {
+        FnvHasher(self.basis)
+    }
+}
+
+/// The FNV-1a hasher built by `FnvState`
+#[derive(Debug, Clone)]
+pub struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.write_u16(n as u16);
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u32(n as u32);
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i128(&mut self, n: i128) {
+        self.write_u128(n as u128);
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.write_u64(n as u64);
+    }
+}
+
+/// One field of a composite key, see `BloomFilter::insert_parts`
+///
+/// `Hash` can't be used as `&dyn Hash`, since its method is generic over
+/// the hasher. This hashes into a `&mut dyn Hasher` instead, and is
+/// implemented for every `Hash` type.
+pub trait KeyPart {
+    /// Feed this part into a hasher, exactly as `Hash::hash` would
+    ///
+    /// # Arguments
+    /// * `state`: hasher to feed
+    fn hash_part(&self, state: &mut dyn Hasher);
+}
+
+impl<T> KeyPart for T
+where
+    T: Hash + ?Sized,
+{
+    fn hash_part(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state);
+    }
+}
+
+/// A composite key that hashes each of its parts in turn
+///
+/// The parts are hashed like the fields of a tuple, with nothing in
+/// between, so `Parts(&[&a, &b])` hashes the same as `(a, b)`.
+pub(crate) struct Parts<'a>(pub &'a [&'a dyn KeyPart]);
+
+impl<'a> Hash for Parts<'a> {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        for part in self.0 {
+            part.hash_part(state);
+        }
+    }
+}
+
+/// How a member's two hashes `h1` and `h2` are turned into its indexes
+///
+/// Every strategy derives the `i`th index from `h1 + i * h2` (Kirsch and
+/// Mitzenmacher's double hashing), which gives essentially the same false
+/// positive rate as independent hashers for a fraction of the work. They
+/// differ in how that is mapped onto the buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IndexStrategy {
+    /// Mix each derived hash, then map it onto the buckets by taking the
+    /// remainder. Slightly favours low indexes unless the number of buckets
+    /// is a power of two.
+    Modulo,
+    /// Mix each derived hash, then map it onto the buckets by multiplying
+    /// and shifting. Uniform for any number of buckets, and the default.
+    #[default]
+    MultiplyShift,
+    /// Reduce `h1` and `h2` first and derive the indexes with modular
+    /// arithmetic, skipping the mixing. The cheapest, but a member's indexes
+    /// may repeat when `h2` shares a factor with the number of buckets.
+    DoubleHash,
+    /// Mix each derived hash, then keep its low bits. Uniform and the
+    /// fastest, but only usable when the number of buckets is a power of two.
+    BitMask,
+}
+
+/// Maps members to the buckets they occupy, see `IndexStrategy`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct Hashers<S> {
+    states: [S; 2],
+    n_hashers: usize,
+    strategy: IndexStrategy,
+    salt: Option<u64>,
+}
+
+impl<S> Hashers<S>
+where
+    S: BuildHasher,
+{
+    /// Create hashers from a factory
+    ///
+    /// # Arguments
+    /// * `n_hashers`: number of indexes to derive per member
+    /// * `state`: called twice to build the two underlying hashers
+    pub fn new<F>(n_hashers: usize, mut state: F) -> Hashers<S>
+    where
+        F: FnMut() -> S,
+    {
+        Hashers::from_states([state(), state()], n_hashers)
+    }
+
+    /// Create hashers from the two underlying hashers
+    ///
+    /// # Arguments
+    /// * `states`: builders of the hashers producing `h1` and `h2`
+    /// * `n_hashers`: number of indexes to derive per member
+    pub fn from_states(states: [S; 2], n_hashers: usize) -> Hashers<S> {
+        Hashers {
+            states,
+            n_hashers,
+            strategy: IndexStrategy::default(),
+            salt: None,
+        }
+    }
+
+    /// The same hashers, deriving indexes with a different strategy
+    ///
+    /// # Arguments
+    /// * `strategy`: how to derive indexes from a member's hashes
+    pub fn with_strategy(self, strategy: IndexStrategy) -> Hashers<S> {
+        Hashers { strategy, ..self }
+    }
+
+    /// The same hashers, deriving a different number of indexes per member
+    ///
+    /// # Arguments
+    /// * `n_hashers`: number of indexes to derive per member
+    pub fn with_len(self, n_hashers: usize) -> Hashers<S> {
+        Hashers { n_hashers, ..self }
+    }
+
+    /// The same hashers, hashing a salt before every member
+    ///
+    /// Hashers that only differ in their salts map the same member to
+    /// unrelated indexes.
+    ///
+    /// # Arguments
+    /// * `salt`: written to each hasher ahead of the member
+    pub fn with_salt(self, salt: u64) -> Hashers<S> {
+        Hashers {
+            salt: Some(salt),
+            ..self
+        }
+    }
+
+    /// The salt hashed before every member, if any
+    pub fn salt(&self) -> Option<u64> {
+        self.salt
+    }
+
+    /// The underlying hashers
+    pub fn states(&self) -> &[S; 2] {
+        &self.states
+    }
+
+    /// Number of indexes derived per member
+    pub fn len(&self) -> usize {
+        self.n_hashers
+    }
+
+    /// How indexes are derived from a member's hashes
+    pub fn strategy(&self) -> IndexStrategy {
+        self.strategy
+    }
+
+    /// The index that an index into a buffer folds to when the buffer is
+    /// shrunk by a whole factor
+    ///
+    /// # Arguments
+    /// * `idx`: index into the original buffer
+    /// * `factor`: number of original buckets per bucket of the new buffer
+    /// * `n_buckets`: number of buckets of the new buffer
+    pub fn fold(&self, idx: usize, factor: usize, n_buckets: usize) -> usize {
+        match self.strategy {
+            IndexStrategy::MultiplyShift => idx / factor,
+            IndexStrategy::Modulo
+            | IndexStrategy::DoubleHash
+            | IndexStrategy::BitMask => idx % n_buckets,
+        }
+    }
+
+    /// The indexes that an element hashes to
+    ///
+    /// # Arguments
+    /// * `e`: element to hash
+    /// * `n_buckets`: number of buckets the indexes must fall within, a power
+    ///   of two for `IndexStrategy::BitMask`
+    pub fn indexes<T>(
+        &self,
+        e: &T,
+        n_buckets: usize,
+    ) -> impl Iterator<Item = usize>
+    where
+        T: Hash + ?Sized,
+    {
+        self.pair_indexes(self.hash_pair(e), n_buckets)
+    }
+
+    /// The pair of hashes `h1` and `h2` that an element's indexes are
+    /// derived from
+    ///
+    /// # Arguments
+    /// * `e`: element to hash
+    pub fn hash_pair<T>(&self, e: &T) -> [u64; 2]
+    where
+        T: Hash + ?Sized,
+    {
+        [self.hash(&self.states[0], e), self.hash(&self.states[1], e)]
+    }
+
+    /// The indexes that a pair of hashes from `hash_pair` maps to
+    ///
+    /// # Arguments
+    /// * `pair`: hashes `h1` and `h2` of an element
+    /// * `n_buckets`: number of buckets the indexes must fall within, a power
+    ///   of two for `IndexStrategy::BitMask`
+    pub fn pair_indexes(
+        &self,
+        pair: [u64; 2],
+        n_buckets: usize,
+    ) -> impl Iterator<Item = usize> {
+        self.derive_indexes(pair[0], pair[1], n_buckets)
+    }
+
+    /// The `n_hashers` full 64-bit hashes of an element, before they are
+    /// mapped onto any buckets
+    ///
+    /// # Arguments
+    /// * `e`: element to hash
+    pub fn hashes<T>(&self, e: &T) -> impl Iterator<Item = u64>
+    where
+        T: Hash,
+    {
+        let h1 = self.hash(&self.states[0], e);
+        let h2 = self.hash(&self.states[1], e);
+
+        (0..self.n_hashers as u64)
+            .map(move |i| mix(h1.wrapping_add(i.wrapping_mul(h2))))
+    }
+
+    /// The indexes that an already computed hash maps to
+    ///
+    /// The hash is used in place of `h1`, and `h2` is derived from it by
+    /// mixing, so neither underlying hasher, nor the salt, is used.
+    ///
+    /// # Arguments
+    /// * `h`: hash of an element
+    /// * `n_buckets`: number of buckets the indexes must fall within, a power
+    ///   of two for `IndexStrategy::BitMask`
+    pub fn hash_indexes(
+        &self,
+        h: u64,
+        n_buckets: usize,
+    ) -> impl Iterator<Item = usize> {
+        self.pair_indexes(self.hashed_pair(h), n_buckets)
+    }
+
+    /// The pair of hashes that `hash_indexes` derives indexes from
+    ///
+    /// # Arguments
+    /// * `h`: hash of an element
+    pub fn hashed_pair(&self, h: u64) -> [u64; 2] {
+        [h, mix(h)]
+    }
+
+    /// Hash a member with one of the underlying hashers, after the salt
+    fn hash<T>(&self, state: &S, e: &T) -> u64
+    where
+        T: Hash + ?Sized,
+    {
+        let mut hasher = state.build_hasher();
+        if let Some(salt) = self.salt {
+            hasher.write_u64(salt);
+        }
+        e.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The indexes for the pair of hashes `h1` and `h2`
+    fn derive_indexes(
+        &self,
+        h1: u64,
+        h2: u64,
+        n_buckets: usize,
+    ) -> impl Iterator<Item = usize> {
+        let strategy = self.strategy;
+
+        (0..self.n_hashers as u64).map(move |i| match strategy {
+            IndexStrategy::Modulo => {
+                let h = mix(h1.wrapping_add(i.wrapping_mul(h2)));
+                (h % n_buckets as u64) as usize
+            }
+            IndexStrategy::MultiplyShift => {
+                reduce(mix(h1.wrapping_add(i.wrapping_mul(h2))), n_buckets)
+            }
+            IndexStrategy::DoubleHash => {
+                let n = n_buckets as u128;
+                let (a, b) = (u128::from(h1) % n, u128::from(h2) % n);
+                ((a + u128::from(i) * b) % n) as usize
+            }
+            IndexStrategy::BitMask => {
+                let h = mix(h1.wrapping_add(i.wrapping_mul(h2)));
+                (h & (n_buckets as u64 - 1)) as usize
+            }
+        })
+    }
+
+    /// Whether other hashers map every member to the same indexes as these
+    ///
+    /// `BuildHasher`s can't be compared directly, but two that hash a fixed
+    /// probe to the same value are assumed to be configured identically.
+    pub fn same_as(&self, other: &Hashers<S>) -> bool {
+        self.n_hashers == other.n_hashers
+            && self.strategy == other.strategy
+            && self.salt == other.salt
+            && self.fingerprints() == other.fingerprints()
+    }
+
+    /// What both underlying hashers hash a fixed probe to
+    fn fingerprints(&self) -> [u64; 2] {
+        [self.states[0].hash_one(0u64), self.states[1].hash_one(0u64)]
+    }
+}
+
+/// Consistent with `same_as`: hashers that are the same hash equal
+impl<S> Hash for Hashers<S>
+where
+    S: BuildHasher,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        self.n_hashers.hash(state);
+        self.strategy.hash(state);
+        self.salt.hash(state);
+        self.fingerprints().hash(state);
+    }
+}
+
+/// Map a hash uniformly onto `0..n` (Lemire's multiply-shift reduction)
+///
+/// Taking `h % n` favours the lowest indexes whenever `n` isn't a power of
+/// two. Instead the hash is treated as a fraction of `2^64` and scaled by `n`,
+/// which spreads hashes over every index as evenly as `n` allows and avoids a
+/// division. Because this uses the high bits of the hash, index `i` of a
+/// buffer of `n * f` buckets always reduces to index `i / f` of one of `n`.
+///
+/// The whole 64 bit hash takes part even where `usize` is 32 bits, and only
+/// the result, which is less than `n`, is narrowed to a `usize`.
+///
+/// # Arguments
+/// * `h`: hash to reduce
+/// * `n`: number of indexes
+pub(crate) fn reduce(h: u64, n: usize) -> usize {
+    ((u128::from(h) * n as u128) >> 64) as usize
+}
+
+/// Number of `SipState`s created so far, used in place of randomness
+#[cfg(all(not(feature = "std"), target_has_atomic = "ptr"))]
+fn next_seed() -> u64 {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static SEED: AtomicUsize = AtomicUsize::new(0);
+    SEED.fetch_add(1, Ordering::Relaxed) as u64
+}
+
+/// Targets without atomics can't count, so every `SipState` is the same
+#[cfg(all(not(feature = "std"), not(target_has_atomic = "ptr")))]
+fn next_seed() -> u64 {
+    0
+}
+
+/// Scramble the bits of a hash (MurmurHash3's finalizer)
+///
+/// Derived hashes differ by a multiple of `h2`, so without mixing the indexes
+/// of a member would be spaced evenly and could land in only a few buckets.
+/// Mixing first makes every derived index independent of that.
+pub(crate) fn mix(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use core::hash::Hasher;
+
+    use crate::test::OffsetState;
+
+    #[test]
+    fn test_same_keys_hash_equal() {
+        let a = SipState::with_keys(1, 2);
+        let b = SipState::with_keys(1, 2);
+
+        assert_eq!(a.hash_one("do add this"), b.hash_one("do add this"));
+    }
+
+    #[test]
+    fn test_indexes_are_deterministic() {
+        let hashers = Hashers::new(5, SipState::new);
+
+        let idxs: Vec<_> = hashers.indexes(&"do add this", 100).collect();
+        assert_eq!(5, idxs.len());
+        assert!(idxs.iter().all(|&idx| idx < 100));
+        assert!(hashers.indexes(&"do add this", 100).eq(idxs));
+    }
+
+    /// Test that a step sharing a factor with the bucket count doesn't make a
+    /// member's indexes repeat
+    #[test]
+    fn test_indexes_do_not_cycle() {
+        let hashers =
+            Hashers::from_states([OffsetState(0), OffsetState(50)], 10);
+
+        let mut idxs: Vec<_> = hashers.indexes(&0u64, 100).collect();
+        idxs.sort();
+        idxs.dedup();
+        assert!(idxs.len() > 2);
+    }
+
+    /// Test that every bucket is hit about as often when the bucket count
+    /// isn't a power of two
+    #[test]
+    fn test_indexes_are_uniform() {
+        let hashers = Hashers::new(1, SipState::new);
+        let mut hits = [0usize; 100];
+        for e in 0..100_000 {
+            for idx in hashers.indexes(&e, hits.len()) {
+                hits[idx] += 1;
+            }
+        }
+
+        // 1000 expected per bucket, with a standard deviation of about 31
+        assert!(hits.iter().all(|&n| n > 800 && n < 1200));
+    }
+
+    #[test]
+    fn test_reduce() {
+        assert_eq!(0, reduce(0, 10));
+        assert_eq!(9, reduce(u64::MAX, 10));
+        assert_eq!(5, reduce(1 << 63, 10));
+    }
+
+    /// Test that hashes differing only in their upper 32 bits, which a
+    /// cast to a 32 bit `usize` would drop, reduce to different indexes
+    #[test]
+    fn test_reduce_uses_high_bits() {
+        let mut idxs: Vec<_> = (0..16).map(|i| reduce(i << 60, 16)).collect();
+        idxs.dedup();
+        assert_eq!(16, idxs.len());
+
+        assert!((0..1000).all(|i| reduce(u64::MAX - i, 16) == 15));
+    }
+
+    const STRATEGIES: [IndexStrategy; 3] = [
+        IndexStrategy::Modulo,
+        IndexStrategy::MultiplyShift,
+        IndexStrategy::DoubleHash,
+    ];
+
+    #[test]
+    fn test_fnv_reference_values() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = FnvState::new().build_hasher();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+
+        assert_eq!(0xcbf2_9ce4_8422_2325, hash(b""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, hash(b"a"));
+        assert_eq!(0x8594_4171_f739_67e8, hash(b"foobar"));
+    }
+
+    #[test]
+    fn test_fnv_is_portable() {
+        let state = FnvState::new();
+
+        let mut bytes = FnvState::new().build_hasher();
+        bytes.write(&[0x78, 0x56, 0x34, 0x12, 0, 0, 0, 0]);
+        assert_eq!(bytes.finish(), state.hash_one(0x1234_5678usize));
+        assert_eq!(bytes.finish(), state.hash_one(0x1234_5678u64));
+    }
+
+    #[test]
+    fn test_salt() {
+        let hashers = Hashers::new(7, || SipState::with_keys(1, 2));
+        let salted = hashers.clone().with_salt(3);
+
+        for e in 0..100 {
+            let unsalted: Vec<usize> = hashers.indexes(&e, 1000).collect();
+            let a: Vec<usize> = salted.indexes(&e, 1000).collect();
+            let b: Vec<usize> =
+                salted.clone().with_salt(4).indexes(&e, 1000).collect();
+
+            assert!(salted.indexes(&e, 1000).eq(a.iter().cloned()));
+            assert_ne!(unsalted, a);
+            assert_ne!(a, b);
+        }
+
+        assert!(!hashers.same_as(&salted));
+        assert!(salted.same_as(&salted.clone()));
+    }
+
+    #[test]
+    fn test_hash_indexes() {
+        let hashers = Hashers::new(7, SipState::new);
+
+        let indexes: Vec<usize> = hashers.hash_indexes(42, 1000).collect();
+        assert_eq!(7, indexes.len());
+        assert!(indexes.iter().all(|&idx| idx < 1000));
+        assert!(hashers.hash_indexes(42, 1000).eq(indexes.iter().cloned()));
+        assert!(!hashers.hash_indexes(43, 1000).eq(indexes.iter().cloned()));
+    }
+
+    #[test]
+    fn test_bit_mask() {
+        let hashers = Hashers::new(7, SipState::new)
+            .with_strategy(IndexStrategy::BitMask);
+
+        for e in 0..100 {
+            assert!(hashers.indexes(&e, 128).all(|idx| idx < 128));
+
+            let folded = hashers
+                .indexes(&e, 512)
+                .map(|idx| hashers.fold(idx, 4, 128));
+            assert!(folded.eq(hashers.indexes(&e, 128)));
+        }
+    }
+
+    #[test]
+    fn test_strategies_are_deterministic_and_in_range() {
+        for &strategy in &STRATEGIES {
+            let hashers =
+                Hashers::new(7, SipState::new).with_strategy(strategy);
+
+            for e in 0..100 {
+                let idxs: Vec<_> = hashers.indexes(&e, 101).collect();
+                assert_eq!(7, idxs.len());
+                assert!(idxs.iter().all(|&idx| idx < 101));
+                assert!(hashers.indexes(&e, 101).eq(idxs));
+            }
+        }
+    }
+
+    /// Test that folding an index agrees with hashing into the smaller
+    /// buffer directly
+    #[test]
+    fn test_fold() {
+        for &strategy in &STRATEGIES {
+            let hashers =
+                Hashers::new(7, SipState::new).with_strategy(strategy);
+
+            for e in 0..100 {
+                let folded = hashers
+                    .indexes(&e, 300)
+                    .map(|idx| hashers.fold(idx, 3, 100));
+                assert!(folded.eq(hashers.indexes(&e, 100)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_as() {
+        let states = [SipState::with_keys(1, 2), SipState::with_keys(3, 4)];
+        let hashers = Hashers::from_states(states, 3);
+
+        assert!(hashers.same_as(&Hashers::from_states(states, 3)));
+        assert!(!hashers.same_as(&Hashers::from_states(states, 4)));
+        assert!(!hashers.same_as(&Hashers::new(3, SipState::new)));
+        assert!(!hashers.same_as(
+            &Hashers::from_states(states, 3)
+                .with_strategy(IndexStrategy::Modulo)
+        ));
+    }
+
+    #[test]
+    fn test_new_keys_differ() {
+        assert_ne!(SipState::new().keys(), SipState::new().keys());
+    }
+}
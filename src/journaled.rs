@@ -0,0 +1,223 @@
+//! A Bloom Filter that records its inserts for recovery
+
+use core::hash::{BuildHasher, Hash};
+use std::io::{self, ErrorKind, Read, Write};
+
+use super::{BloomFilter, FnvState};
+
+/// Length of each record in the journal
+const RECORD_LEN: usize = 8;
+
+/// Bloom Filter that appends every insert to a journal before applying it
+///
+/// Each member is hashed once, with `FnvState`, and the 64-bit hash is
+/// written to the journal as a little-endian record and then inserted with
+/// `BloomFilter::insert_hash`. After a crash, `replay` rebuilds the filter
+/// from the journal alone, on any platform, without the original data. The
+/// journal should be flushed, or synced for durability, by the caller as
+/// often as needed.
+///
+/// Only available with the `std` feature.
+#[derive(Debug)]
+pub struct JournaledBloomFilter<W> {
+    filter: BloomFilter,
+    journal: W,
+}
+
+impl<W> JournaledBloomFilter<W>
+where
+    W: Write,
+{
+    /// Build an empty Journaled Bloom Filter with a specified false positive
+    /// rate
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `journal`: sink that inserts are appended to
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new(
+        n_elems: usize,
+        fp_rate: f32,
+        journal: W,
+    ) -> JournaledBloomFilter<W> {
+        JournaledBloomFilter {
+            filter: BloomFilter::new_with_fp(n_elems, fp_rate),
+            journal,
+        }
+    }
+
+    /// Rebuild a Journaled Bloom Filter from a journal written by one, along
+    /// with the length in bytes of the whole records that were replayed
+    ///
+    /// `n_elems` and `fp_rate` must be the ones the journaled filter was
+    /// built with. A partial record at the end, left by a crash during a
+    /// write, is ignored. Replayed inserts aren't written to `journal`, so
+    /// it can be the same file, opened for appending, but it must first be
+    /// truncated to the returned length (e.g. with `File::set_len`).
+    /// Otherwise records appended after a partial one are misaligned, and
+    /// the next replay inserts the wrong hashes.
+    ///
+    /// # Arguments
+    /// * `reader`: journal to replay
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `journal`: sink that further inserts are appended to
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn replay<R>(
+        mut reader: R,
+        n_elems: usize,
+        fp_rate: f32,
+        journal: W,
+    ) -> io::Result<(JournaledBloomFilter<W>, u64)>
+    where
+        R: Read,
+    {
+        let mut filter = JournaledBloomFilter::new(n_elems, fp_rate, journal);
+
+        let mut record = [0; RECORD_LEN];
+        let mut valid_len = 0;
+        while read_record(&mut reader, &mut record)? {
+            filter.filter.insert_hash(u64::from_le_bytes(record));
+            valid_len += RECORD_LEN as u64;
+        }
+
+        Ok((filter, valid_len))
+    }
+
+    /// Journal and insert a member
+    ///
+    /// Returns whether at least one new bit was set, like
+    /// `BloomFilter::insert`. The member is only inserted once it has been
+    /// written to the journal.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T) -> io::Result<bool>
+    where
+        T: Hash,
+    {
+        let h = FnvState::new().hash_one(e);
+        self.journal.write_all(&h.to_le_bytes())?;
+
+        Ok(self.filter.insert_hash(h))
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.filter.may_contain_hash(FnvState::new().hash_one(e))
+    }
+
+    /// The filter being journaled
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+
+    /// The journal, e.g. to flush it
+    pub fn journal_mut(&mut self) -> &mut W {
+        &mut self.journal
+    }
+
+    /// Stop journaling, returning the filter and the journal
+    pub fn into_parts(self) -> (BloomFilter, W) {
+        (self.filter, self.journal)
+    }
+}
+
+/// Read a whole record, returning false if the reader ends first
+fn read_record<R>(reader: &mut R, record: &mut [u8]) -> io::Result<bool>
+where
+    R: Read,
+{
+    let mut filled = 0;
+    while filled < record.len() {
+        match reader.read(&mut record[filled..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::sink;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_replay() {
+        let mut filter = JournaledBloomFilter::new(100, 0.01, Vec::new());
+        for e in 0..100 {
+            filter.insert(&e).unwrap();
+        }
+        let (original, journal) = filter.into_parts();
+        assert_eq!(100 * RECORD_LEN, journal.len());
+
+        let (replayed, valid_len) =
+            JournaledBloomFilter::replay(&journal[..], 100, 0.01, sink())
+                .unwrap();
+        assert_eq!(journal.len() as u64, valid_len);
+        assert_eq!(original.as_bitvec(), replayed.filter().as_bitvec());
+        assert!((0..100).all(|e| replayed.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_replay_ignores_torn_record() {
+        let mut filter = JournaledBloomFilter::new(10, 0.01, Vec::new());
+        filter.insert(&"do add this").unwrap();
+        let (_, mut journal) = filter.into_parts();
+        journal.extend_from_slice(&[1, 2, 3]);
+
+        let (replayed, valid_len) =
+            JournaledBloomFilter::replay(&journal[..], 10, 0.01, sink())
+                .unwrap();
+        assert!(replayed.may_contain(&"do add this"));
+        assert_eq!(1, replayed.filter().size());
+        assert_eq!(RECORD_LEN as u64, valid_len);
+    }
+
+    #[test]
+    fn test_append_after_torn_record() {
+        let mut filter = JournaledBloomFilter::new(10, 0.01, Vec::new());
+        filter.insert(&"before the crash").unwrap();
+        let (_, mut journal) = filter.into_parts();
+        journal.extend_from_slice(&[1, 2, 3]);
+
+        let (_, valid_len) =
+            JournaledBloomFilter::replay(&journal[..], 10, 0.01, sink())
+                .unwrap();
+        journal.truncate(valid_len as usize);
+        let (mut filter, _) = JournaledBloomFilter::replay(
+            &journal[..],
+            10,
+            0.01,
+            journal.clone(),
+        )
+        .unwrap();
+        filter.insert(&"after the crash").unwrap();
+        let (_, journal) = filter.into_parts();
+
+        let (replayed, valid_len) =
+            JournaledBloomFilter::replay(&journal[..], 10, 0.01, sink())
+                .unwrap();
+        assert_eq!(journal.len() as u64, valid_len);
+        assert!(replayed.may_contain(&"before the crash"));
+        assert!(replayed.may_contain(&"after the crash"));
+        assert_eq!(2, replayed.filter().size());
+    }
+}
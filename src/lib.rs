@@ -1,61 +1,256 @@
 //! A space-efficient, probabilistic data structure
+//!
+//! The `std` feature is enabled by default. Without it the crate is
+//! `no_std` and only needs `alloc`, but the `libm` feature must be enabled
+//! instead to provide floating point math.
+
+#![no_std]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("either the `std` or the `libm` feature must be enabled");
+
+#[macro_use]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+extern crate libm;
 
 extern crate bit_vec;
-use bit_vec::BitVec;
+/// Re-exported for `BloomFilter::as_bitvec` and `may_contain_many`
+pub use bit_vec::BitVec;
+
+extern crate siphasher;
+
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "rand_core")]
+extern crate rand_core;
+#[cfg(feature = "rand_core")]
+use rand_core::RngCore;
+
+#[cfg(all(test, feature = "rand_core"))]
+extern crate rand_pcg;
+
+#[cfg(all(test, feature = "serde"))]
+extern crate bincode;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+use alloc::vec::Vec;
+use core::f32::consts::LN_2;
+use core::fmt;
+use core::hash::BuildHasher;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::iter::FromIterator;
+use core::mem;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead};
+#[cfg(feature = "std")]
+use std::string::String;
+
+mod arena;
+pub use arena::{BloomFilterArena, FilterId};
+
+#[cfg(target_has_atomic = "64")]
+mod atomic;
+#[cfg(target_has_atomic = "64")]
+pub use atomic::AtomicBloomFilter;
+
+mod blocked;
+pub use blocked::BlockedBloomFilter;
+
+mod builder;
+pub use builder::BloomFilterBuilder;
+
+mod codec;
+
+mod compressed;
+pub use compressed::CompressedBloomFilter;
 
-use std::hash::Hash;
-use std::hash::Hasher;
-use std::hash::BuildHasher;
+mod count_min;
+pub use count_min::CountMinSketch;
 
-use std::collections::hash_map::RandomState;
+mod counting;
+pub use counting::{CounterWidth, CountingBloomFilter};
 
-/// Calculate the probability of getting a false positive
+#[cfg(feature = "std")]
+mod debug;
+#[cfg(feature = "std")]
+pub use debug::DebugBloomFilter;
+
+mod error;
+pub use error::{BuildError, DecodeError, MergeError, SaturatedError};
+
+mod frozen;
+pub use frozen::FrozenBloomFilter;
+
+mod hashing;
+use hashing::{mix, reduce, seeded, Hashers, Parts, PORTABLE_BASES};
+pub use hashing::{FnvHasher, FnvState, IndexStrategy, KeyPart, SipState};
+
+#[cfg(feature = "std")]
+mod journaled;
+#[cfg(feature = "std")]
+pub use journaled::JournaledBloomFilter;
+
+mod math;
+
+mod min_hash;
+pub use min_hash::MinHash;
+
+mod partitioned;
+pub use partitioned::PartitionedBloomFilter;
+
+mod rotating;
+pub use rotating::RotatingBloomFilter;
+
+mod scalable;
+pub use scalable::ScalableBloomFilter;
+
+pub mod sizing;
+use sizing::{
+    false_positive_rate, max_elems_at_fp_rate, min_n_buckets, optimal_n_hashers,
+};
+
+pub mod testing;
+
+/// Check that a false positive rate is strictly between 0.0 and 1.0
 ///
 /// # Arguments
-/// * `n_buckets`: number of buckets
-/// * `n_hashers`: number of hashers
-/// * `n_elems`: number of elements
-fn false_positive_rate(
-    n_buckets: usize,
-    n_hashers: usize,
-    n_elems: usize,
-) -> f32 {
-    let k = n_hashers as f32;
-    let n = n_elems as f32;
-    let m = n_buckets as f32;
+/// * `fp_rate`: false positive rate to check
+fn check_fp_rate(fp_rate: f32) -> Result<(), BuildError> {
+    if fp_rate > 0. && fp_rate < 1. {
+        Ok(())
+    } else {
+        Err(BuildError::InvalidFpRate)
+    }
+}
+
+/// Set the buckets at the indexes of a member, returning whether any of them
+/// were previously unset
+///
+/// # Arguments
+/// * `buffer`: buckets the indexes fall within
+/// * `indexes`: indexes of the member
+fn set_indexes<I>(buffer: &mut BitVec, indexes: I) -> bool
+where
+    I: Iterator<Item = usize>,
+{
+    let mut is_new = false;
+    for idx in indexes {
+        is_new |= !buffer.get(idx).unwrap();
+        buffer.set(idx, true);
+    }
 
-    (1. - ((-k * n) / m).exp()).powf(k)
+    is_new
 }
 
-/// Minumum number of buckets required to achieve a target false positive rate
+/// Whether the buckets at the indexes of a member are all set
+///
+/// A member without any indexes is never present, so that a filter without
+/// hashers doesn't report everything as present.
 ///
 /// # Arguments
-/// * `n_elems`: target number of elements
-/// * `fp_rate`: target false positive rate
-fn min_n_buckets(n_elems: usize, fp_rate: f32) -> usize {
-    let n = n_elems as f32;
+/// * `buffer`: buckets the indexes fall within
+/// * `indexes`: indexes of the member
+fn indexes_are_set<I>(buffer: &BitVec, indexes: I) -> bool
+where
+    I: Iterator<Item = usize>,
+{
+    let mut n_indexes = 0;
+    for idx in indexes {
+        if !buffer.get(idx).unwrap() {
+            return false;
+        }
+        n_indexes += 1;
+    }
 
-    (-1. * n * fp_rate.ln() / (2f32.ln().powf(2.))).ceil() as usize
+    n_indexes > 0
 }
 
-/// Calculate the optimal number of hashers
+/// Estimate the number of distinct elements in a filter
 ///
 /// # Arguments
 /// * `n_buckets`: number of buckets
-/// * `n_elems`: number of elemements
-fn optimal_n_hashers(n_buckets: usize, n_elems: usize) -> usize {
-    let n = n_elems as f32;
+/// * `n_hashers`: number of hashers
+/// * `n_set`: number of buckets that are set
+fn estimate_cardinality(
+    n_buckets: usize,
+    n_hashers: usize,
+    n_set: usize,
+) -> usize {
+    let k = n_hashers as f32;
     let m = n_buckets as f32;
+    let x = n_set as f32;
 
-    ((m / n) * 2f32.ln()).ceil() as usize
+    math::round(-(m / k) * math::ln(1. - x / m)) as usize
 }
 
+/// False positive rate of filters sized automatically, such as by `collect`
+pub const DEFAULT_FP_RATE: f32 = 0.01;
+
+/// Number of elements that `BloomFilter::default` is sized for
+pub const DEFAULT_CAPACITY: usize = 1000;
+
 /// Space-efficient probabilistic hash set
-#[derive(Debug)]
-pub struct BloomFilter {
+///
+/// Members are hashed with hashers built from `S`, `SipState` by default.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BloomFilter<S = SipState> {
     buffer: BitVec,
     size: usize,
-    hashers: Vec<RandomState>,
+    capacity: usize,
+    design_fp_rate: f32,
+    hashers: Hashers<S>,
+    /// Hashes of every member inserted since auto growth was enabled, see
+    /// `auto_grow`
+    #[cfg_attr(feature = "serde", serde(default))]
+    grow_log: Option<Vec<[u64; 2]>>,
+}
+
+/// What an insert found in the buckets of the inserted member
+///
+/// See `BloomFilter::insert_with_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsertStats {
+    /// Number of the member's indexes whose bucket was already set
+    pub already_set: usize,
+    /// Number of indexes the member hashes to
+    pub n_hashers: usize,
+}
+
+impl InsertStats {
+    /// Whether at least one new bit was set, as returned by `insert`
+    pub fn is_new(&self) -> bool {
+        self.already_set < self.n_hashers
+    }
+}
+
+/// How a filter hashes members into buckets, without any of its buckets
+///
+/// Filters built from the same configuration with
+/// `BloomFilter::with_config` hash every member to the same buckets, so they
+/// can be unioned, intersected and compared. See `BloomFilter::hasher_config`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HasherConfig<S = SipState> {
+    /// The two underlying hashers, such as `SipState`s with known keys
+    pub states: [S; 2],
+    /// Number of indexes derived per member
+    pub n_hashers: usize,
+    /// Number of buckets the indexes address
+    pub buckets: usize,
+    /// How indexes are derived from a member's hashes
+    pub strategy: IndexStrategy,
+    /// Hashed before every member, if set
+    pub salt: Option<u64>,
 }
 
 impl BloomFilter {
@@ -64,152 +259,3034 @@ impl BloomFilter {
     /// # Arguments
     /// * `n_elems`: expected number of elements
     /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
     pub fn new_with_fp(n_elems: usize, fp_rate: f32) -> BloomFilter {
-        let min_buckets = min_n_buckets(n_elems, fp_rate);
-        let n_hashers = optimal_n_hashers(min_buckets, n_elems);
+        BloomFilter::new_with_fp_and_hasher(n_elems, fp_rate, SipState::new)
+    }
 
-        BloomFilter {
-            size: 0,
-            buffer: BitVec::from_elem(min_buckets, false),
-            hashers: (0..n_hashers).map(|_| RandomState::new()).collect(),
-        }
+    /// Build a Bloom Filter with a specified false positive rate, failing if
+    /// the rate isn't strictly between 0.0 and 1.0
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    pub fn try_new_with_fp(
+        n_elems: usize,
+        fp_rate: f32,
+    ) -> Result<BloomFilter, BuildError> {
+        BloomFilter::try_new_with_fp_and_hasher(n_elems, fp_rate, SipState::new)
+    }
+
+    /// Build a Bloom Filter with a specified false positive rate, along with
+    /// the rate it actually achieves
+    ///
+    /// Rounding the numbers of buckets and hashers to whole numbers makes
+    /// the rate at `n_elems` elements differ slightly from the requested one,
+    /// in either direction. Fails if the requested rate isn't strictly
+    /// between 0.0 and 1.0.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    pub fn new_with_fp_checked(
+        n_elems: usize,
+        fp_rate: f32,
+    ) -> Result<(BloomFilter, f32), BuildError> {
+        let filter = BloomFilter::try_new_with_fp(n_elems, fp_rate)?;
+        let achieved = filter.projected_fp_rate(n_elems);
+        Ok((filter, achieved))
     }
 
     /// Create a new Bloom Filter with specified buffer size
     ///
     /// # Arguments
     /// * `n_elems`: expected number of elements
-    /// * `size`: desired buffer size
+    /// * `size`: desired buffer size, rounded up to 1 if zero
     pub fn new_with_size(n_elems: usize, size: usize) -> BloomFilter {
-        let n_hashers = optimal_n_hashers(size, n_elems);
+        BloomFilter::new_with_size_and_hasher(n_elems, size, SipState::new)
+    }
+
+    /// Create a new Bloom Filter that fits in a memory budget
+    ///
+    /// The budget covers everything `memory_bytes` counts, so the buffer gets
+    /// whatever is left after the filter itself, in whole storage blocks. If
+    /// nothing is left, the filter has a single bucket and exceeds the
+    /// budget.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `max_bytes`: most memory the filter may use, in bytes
+    pub fn new_with_memory(n_elems: usize, max_bytes: usize) -> BloomFilter {
+        let block_bytes = mem::size_of::<u32>();
+        let buffer_bytes =
+            max_bytes.saturating_sub(mem::size_of::<BloomFilter>());
+        let size = buffer_bytes / block_bytes * block_bytes * 8;
+
+        BloomFilter::new_with_size(n_elems, size)
+    }
+
+    /// Create a new Bloom Filter with an exact number of hashers
+    ///
+    /// Useful to match a filter built elsewhere. The filter is taken to be
+    /// designed for the number of elements that `n_hashers` is optimal for,
+    /// but at least 1.
+    ///
+    /// # Arguments
+    /// * `size`: desired buffer size, rounded up to 1 if zero
+    /// * `n_hashers`: number of hashers, at least 1
+    ///
+    /// # Panics
+    /// If `n_hashers` is zero
+    pub fn new_with_size_and_hashers(
+        size: usize,
+        n_hashers: usize,
+    ) -> BloomFilter {
+        if n_hashers == 0 {
+            panic!("{}", BuildError::NoHashers);
+        }
+
+        let size = size.max(1);
+        let n_elems = ((size as f32 * LN_2 / n_hashers as f32) as usize).max(1);
 
         BloomFilter {
             size: 0,
             buffer: BitVec::from_elem(size, false),
-            hashers: (0..n_hashers).map(|_| RandomState::new()).collect(),
+            capacity: n_elems,
+            design_fp_rate: false_positive_rate(size, n_hashers, n_elems),
+            hashers: Hashers::new(n_hashers, SipState::new),
+            grow_log: None,
         }
     }
 
-    /// Insert a member
+    /// Build a Bloom Filter sized for exactly the given elements
+    ///
+    /// The elements are buffered first so they can be counted. Duplicates
+    /// are counted too, so the filter is oversized if there are any.
     ///
     /// # Arguments
-    /// * `e`: element to add
-    pub fn insert<T>(&mut self, e: &T)
+    /// * `elems`: elements to add
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn from_exact<T, I>(elems: I, fp_rate: f32) -> BloomFilter
     where
         T: Hash,
+        I: IntoIterator<Item = T>,
     {
-        for idx in self.indexes(e) {
-            self.buffer.set(idx, true);
+        BloomFilter::from_exact_with_hasher(elems, fp_rate, SipState::new)
+    }
+
+    /// Build a Bloom Filter from the lines of a reader
+    ///
+    /// Lines are read one at a time and inserted as `str`s, without their
+    /// line ending, so only one line is held in memory at once. Look them up
+    /// with a `str` (or `String`) too.
+    ///
+    /// # Arguments
+    /// * `reader`: source of newline-delimited elements
+    /// * `n_elems`: expected number of lines
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    #[cfg(feature = "std")]
+    pub fn from_reader<R>(
+        mut reader: R,
+        n_elems: usize,
+        fp_rate: f32,
+    ) -> io::Result<BloomFilter>
+    where
+        R: BufRead,
+    {
+        let mut filter = BloomFilter::new_with_fp(n_elems, fp_rate);
+
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            let elem = line.strip_suffix('\n').unwrap_or(&line);
+            let elem = elem.strip_suffix('\r').unwrap_or(elem);
+            filter.insert(&elem);
+            line.clear();
         }
 
-        self.size += 1;
+        Ok(filter)
     }
 
-    /// Check membership
+    /// Create a new Bloom Filter whose hashers are built from known keys
+    ///
+    /// Filters built from the same parameters and seeds hash every member to
+    /// the same buckets, even across processes.
     ///
     /// # Arguments
-    /// * `e`: element to check membership of
-    pub fn may_contain<T>(&self, e: &T) -> bool
+    /// * `n_elems`: expected number of elements
+    /// * `size`: desired buffer size, rounded up to 1 if zero
+    /// * `seeds`: keys for the two underlying hashers, see
+    ///   `SipState::with_keys`
+    pub fn new_with_seeds(
+        n_elems: usize,
+        size: usize,
+        seeds: [(u64, u64); 2],
+    ) -> BloomFilter {
+        BloomFilter::new_with_size_and_hasher(n_elems, size, seeded(seeds))
+    }
+
+    /// Build a Bloom Filter with a specified false positive rate whose
+    /// hashers are keyed from a random number generator
+    ///
+    /// A seeded generator gives the same filter every time, and works
+    /// without the `std` feature's random keys. Only available with the
+    /// `rand_core` feature.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `rng`: generator to draw the keys of the hashers from
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    #[cfg(feature = "rand_core")]
+    pub fn new_with_rng<R>(
+        n_elems: usize,
+        fp_rate: f32,
+        rng: &mut R,
+    ) -> BloomFilter
     where
-        T: Hash,
+        R: RngCore,
     {
-        let mut may_contain = true;
+        let seeds = [
+            (rng.next_u64(), rng.next_u64()),
+            (rng.next_u64(), rng.next_u64()),
+        ];
+        BloomFilter::new_with_fp_and_hasher(n_elems, fp_rate, seeded(seeds))
+    }
+
+    /// Build a Bloom Filter with a specified false positive rate that hashes
+    /// a salt before every member
+    ///
+    /// Filters with different salts map the same member to unrelated
+    /// buckets, even when their hashers are otherwise the same, which keeps
+    /// logically separate filters apart.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `salt`: hashed ahead of every member
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_salt(
+        n_elems: usize,
+        fp_rate: f32,
+        salt: u64,
+    ) -> BloomFilter {
+        let mut filter = BloomFilter::new_with_fp(n_elems, fp_rate);
+        filter.hashers = filter.hashers.with_salt(salt);
+        filter
+    }
 
-        for idx in self.indexes(e) {
-            may_contain &= self.buffer.get(idx).unwrap();
+    /// Build a Bloom Filter with a specified false positive rate that derives
+    /// indexes with the given strategy
+    ///
+    /// `IndexStrategy::BitMask` needs a power of two number of buckets, so
+    /// the filter is built as by `new_pow2_with_fp` for it.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `strategy`: how to derive indexes from a member's hashes
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_strategy(
+        n_elems: usize,
+        fp_rate: f32,
+        strategy: IndexStrategy,
+    ) -> BloomFilter {
+        if strategy == IndexStrategy::BitMask {
+            return BloomFilter::new_pow2_with_fp(n_elems, fp_rate);
         }
 
-        may_contain
+        let mut filter = BloomFilter::new_with_fp(n_elems, fp_rate);
+        filter.hashers = filter.hashers.with_strategy(strategy);
+        filter
     }
 
-    /// Number of elements in the `BloomFilter`
-    pub fn size(&self) -> usize {
-        self.size
-    }
+    /// Build a Bloom Filter with a specified false positive rate and a power
+    /// of two number of buckets
+    ///
+    /// Indexes are then derived by masking off the low bits of each hash
+    /// (`IndexStrategy::BitMask`), which is cheaper than any other strategy.
+    /// Rounding up can almost double the memory used, though the extra
+    /// buckets also lower the false positive rate.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_pow2_with_fp(n_elems: usize, fp_rate: f32) -> BloomFilter {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
 
-    /// Number of buckets that a memebr can occupy
-    pub fn buckets(&self) -> usize {
-        self.buffer.capacity()
+        let n_buckets = min_n_buckets(n_elems, fp_rate).next_power_of_two();
+        let mut filter = BloomFilter::new_with_size(n_elems, n_buckets);
+        filter.hashers = filter.hashers.with_strategy(IndexStrategy::BitMask);
+        filter.design_fp_rate = fp_rate;
+        filter
     }
 
-    /// Number of hashers being used
-    pub fn n_hashers(&self) -> usize {
-        self.hashers.len()
+    /// Resize a filter in place for a new number of elements and false
+    /// positive rate
+    ///
+    /// Every member is lost: the buffer is emptied and resized, and the
+    /// number of hashers is recomputed. The hashers keep their keys, salt and
+    /// `IndexStrategy`, so filters reprovisioned alike stay compatible, and
+    /// the buckets are rounded up to a power of two for
+    /// `IndexStrategy::BitMask`. The buffer's allocation is reused where it
+    /// is large enough.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn reprovision(&mut self, n_elems: usize, fp_rate: f32) {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        let mut n_buckets = min_n_buckets(n_elems, fp_rate);
+        if self.index_strategy() == IndexStrategy::BitMask {
+            n_buckets = n_buckets.next_power_of_two();
+        }
+        let n_hashers = optimal_n_hashers(n_buckets, n_elems);
+
+        self.buffer.clear();
+        if n_buckets > self.buffer.len() {
+            let len = self.buffer.len();
+            self.buffer.grow(n_buckets - len, false);
+        } else {
+            self.buffer.truncate(n_buckets);
+        }
+
+        self.size = 0;
+        self.capacity = n_elems;
+        self.design_fp_rate = fp_rate;
+        self.hashers = self.hashers.clone().with_len(n_hashers);
+        if let Some(ref mut log) = self.grow_log {
+            log.clear();
+        }
     }
+}
 
-    /// False positive rate
-    pub fn fp_rate(&self) -> f32 {
-        false_positive_rate(self.buckets(), self.n_hashers(), self.size())
+impl BloomFilter<FnvState> {
+    /// Build a Bloom Filter that maps members to the same buckets on every
+    /// platform
+    ///
+    /// Members are hashed with fixed `FnvState`s, so filters built with the
+    /// same parameters set the same buckets for the same members anywhere,
+    /// and can be shared between hosts.
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_portable(n_elems: usize, fp_rate: f32) -> BloomFilter<FnvState> {
+        let mut bases = PORTABLE_BASES.iter();
+        BloomFilter::new_with_fp_and_hasher(n_elems, fp_rate, || {
+            FnvState::with_basis(*bases.next().unwrap())
+        })
     }
+}
 
-    /// The indexes that a element hashes to
-    fn indexes<T>(&self, e: &T) -> Vec<usize>
+impl<S> BloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    /// Build a Bloom Filter with a specified false positive rate and hasher
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `hasher`: called twice to build the underlying hashers
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_fp_and_hasher<F>(
+        n_elems: usize,
+        fp_rate: f32,
+        hasher: F,
+    ) -> BloomFilter<S>
     where
-        T: Hash,
+        F: FnMut() -> S,
     {
-        let mut idxs = vec![];
-        for h in &self.hashers {
-            let mut hasher = h.build_hasher();
-            e.hash(&mut hasher);
-            idxs.push(hasher.finish() as usize % self.buffer.len());
+        match BloomFilter::try_new_with_fp_and_hasher(n_elems, fp_rate, hasher)
+        {
+            Ok(filter) => filter,
+            Err(e) => panic!("{}", e),
         }
-        idxs
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
 
-    /// Test that the bloom filter will always return the same results
-    #[test]
-    fn test_is_deterministic() {
-        let to_add = "do add this";
-        let dont_add = 123;
-        let mut filter = BloomFilter::new_with_size(1, 100);
-        filter.insert(&to_add);
+    /// Build a Bloom Filter with a specified false positive rate and hasher,
+    /// failing if the rate isn't strictly between 0.0 and 1.0
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `hasher`: called twice to build the underlying hashers
+    pub fn try_new_with_fp_and_hasher<F>(
+        n_elems: usize,
+        fp_rate: f32,
+        hasher: F,
+    ) -> Result<BloomFilter<S>, BuildError>
+    where
+        F: FnMut() -> S,
+    {
+        check_fp_rate(fp_rate)?;
 
-        // Check membership twice to make sure that the results are reproducable
-        // even though the hashers are being reset
-        assert_eq!(true, filter.may_contain(&to_add));
-        assert_eq!(true, filter.may_contain(&to_add));
+        let min_buckets = min_n_buckets(n_elems, fp_rate);
+        let mut filter =
+            BloomFilter::new_with_size_and_hasher(n_elems, min_buckets, hasher);
+        filter.design_fp_rate = fp_rate;
 
-        assert_eq!(false, filter.may_contain(&dont_add));
-        assert_eq!(false, filter.may_contain(&dont_add));
+        Ok(filter)
     }
 
-    /// Test that any element that implements the `Hash` trait can be added to
-    /// the `BloomFilter`
-    #[test]
-    fn test_is_heterogenous() {
-        let a = "do add this";
-        let b = 1234567;
+    /// Build a Bloom Filter with a specified hasher, sized for exactly the
+    /// given elements
+    ///
+    /// See `BloomFilter::from_exact`.
+    ///
+    /// # Arguments
+    /// * `elems`: elements to add
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    /// * `hasher`: called twice to build the underlying hashers
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn from_exact_with_hasher<T, I, F>(
+        elems: I,
+        fp_rate: f32,
+        hasher: F,
+    ) -> BloomFilter<S>
+    where
+        T: Hash,
+        I: IntoIterator<Item = T>,
+        F: FnMut() -> S,
+    {
+        let elems: Vec<T> = elems.into_iter().collect();
 
-        let mut filter = BloomFilter::new_with_fp(2, 0.01);
-        filter.insert(&a);
-        filter.insert(&b);
+        let mut filter =
+            BloomFilter::new_with_fp_and_hasher(elems.len(), fp_rate, hasher);
+        filter.extend(elems);
+        filter
+    }
 
-        assert!(filter.may_contain(&a));
-        assert!(filter.may_contain(&a));
-        assert!(filter.may_contain(&b));
-        assert!(filter.may_contain(&b));
+    /// Create a new Bloom Filter with specified buffer size and hasher
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `size`: desired buffer size, rounded up to 1 if zero
+    /// * `hasher`: called twice to build the underlying hashers
+    pub fn new_with_size_and_hasher<F>(
+        n_elems: usize,
+        size: usize,
+        hasher: F,
+    ) -> BloomFilter<S>
+    where
+        F: FnMut() -> S,
+    {
+        let size = size.max(1);
+        let n_hashers = optimal_n_hashers(size, n_elems);
+
+        BloomFilter {
+            size: 0,
+            buffer: BitVec::from_elem(size, false),
+            capacity: n_elems,
+            design_fp_rate: false_positive_rate(size, n_hashers, n_elems),
+            hashers: Hashers::new(n_hashers, hasher),
+            grow_log: None,
+        }
     }
 
-    #[test]
-    fn test_size_increments() {
-        let to_add = "do add this";
+    /// Build an empty Bloom Filter that hashes members as described by a
+    /// configuration
+    ///
+    /// The filter is sized for the number of elements at which its hashers
+    /// are optimal.
+    ///
+    /// # Arguments
+    /// * `config`: hashers and geometry, usually from `hasher_config`; zero
+    ///   buckets are rounded up to 1
+    ///
+    /// # Panics
+    /// If `config` has no hashers, or uses `IndexStrategy::BitMask` with a
+    /// number of buckets that isn't a power of two
+    pub fn with_config(config: HasherConfig<S>) -> BloomFilter<S> {
+        if config.n_hashers == 0 {
+            panic!("{}", BuildError::NoHashers);
+        }
 
-        let mut filter = BloomFilter::new_with_size(3, 100);
-        filter.insert(&to_add);
-        filter.insert(&to_add);
-        filter.insert(&to_add);
+        let size = config.buckets.max(1);
+        if config.strategy == IndexStrategy::BitMask && !size.is_power_of_two()
+        {
+            panic!("{}", BuildError::ConflictingParameters);
+        }
 
-        assert_eq!(3, filter.size());
-    }
+        let n_elems = (size as f32 * LN_2 / config.n_hashers as f32) as usize;
 
-    #[test]
-    fn test_fp_rate_is_zero_no_elems() {
-        let filter = BloomFilter::new_with_size(100, 100);
-        assert_eq!(0.0, filter.fp_rate());
+        let mut hashers = Hashers::from_states(config.states, config.n_hashers)
+            .with_strategy(config.strategy);
+        if let Some(salt) = config.salt {
+            hashers = hashers.with_salt(salt);
+        }
+
+        BloomFilter {
+            size: 0,
+            buffer: BitVec::from_elem(size, false),
+            capacity: n_elems,
+            design_fp_rate: false_positive_rate(
+                size,
+                config.n_hashers,
+                n_elems,
+            ),
+            hashers,
+            grow_log: None,
+        }
+    }
+
+    /// Build a Bloom Filter from an existing buffer and the hashers that
+    /// filled it
+    ///
+    /// The buffer is used as is, so every member that set its bits is
+    /// reported as present as long as `states` and `n_hashers` match the ones
+    /// used to insert it, with the default `IndexStrategy`. The filter is
+    /// taken to be designed for `size` elements, so `try_insert` refuses any
+    /// more.
+    ///
+    /// # Arguments
+    /// * `buffer`: buckets of the filter, must not be empty
+    /// * `size`: number of elements the buffer holds
+    /// * `states`: builders of the two underlying hashers
+    /// * `n_hashers`: number of indexes derived per member, must not be zero
+    pub fn from_parts(
+        buffer: BitVec,
+        size: usize,
+        states: [S; 2],
+        n_hashers: usize,
+    ) -> Result<BloomFilter<S>, BuildError> {
+        if buffer.is_empty() {
+            return Err(BuildError::NoBuckets);
+        }
+
+        if n_hashers == 0 {
+            return Err(BuildError::NoHashers);
+        }
+
+        Ok(BloomFilter {
+            capacity: size,
+            design_fp_rate: false_positive_rate(buffer.len(), n_hashers, size),
+            buffer,
+            size,
+            hashers: Hashers::from_states(states, n_hashers),
+            grow_log: None,
+        })
+    }
+
+    /// Insert a member
+    ///
+    /// Returns whether at least one new bit was set, meaning the member was
+    /// definitely not present before. The size only grows when this is true,
+    /// so inserting a member again leaves it unchanged.
+    ///
+    /// Inserting more elements than the filter was built for keeps working,
+    /// but raises the false positive rate past the one it was designed for.
+    /// See `should_resize`, or `try_insert` to refuse them instead.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        let pair = self.hashers.hash_pair(e);
+        self.insert_pair(pair)
+    }
+
+    /// Insert a byte string
+    ///
+    /// The same as inserting the slice with `insert`, so either of
+    /// `may_contain_bytes` and `may_contain` finds it.
+    ///
+    /// # Arguments
+    /// * `bytes`: element to add
+    pub fn insert_bytes(&mut self, bytes: &[u8]) -> bool {
+        let pair = self.hashers.hash_pair(bytes);
+        self.insert_pair(pair)
+    }
+
+    /// Insert a member by a hash of it that was already computed
+    ///
+    /// The hash replaces both underlying hashers, so members inserted this
+    /// way can only be found with `may_contain_hash` and the same hash.
+    /// Returns whether at least one new bit was set, like `insert`.
+    ///
+    /// # Arguments
+    /// * `h`: 64-bit hash of the element to add
+    pub fn insert_hash(&mut self, h: u64) -> bool {
+        let pair = self.hashers.hashed_pair(h);
+        self.insert_pair(pair)
+    }
+
+    /// Insert a member made of several parts, without building a tuple
+    ///
+    /// The parts are hashed in order, exactly like the fields of a tuple, so
+    /// inserting `&[&a, &b]` is the same as inserting `&(a, b)`, and either
+    /// of `may_contain_parts` and `may_contain` finds it.
+    ///
+    /// # Arguments
+    /// * `parts`: fields of the element to add, in order
+    pub fn insert_parts(&mut self, parts: &[&dyn KeyPart]) -> bool {
+        self.insert(&Parts(parts))
+    }
+
+    /// Insert a member, reporting how many of its buckets were already set
+    ///
+    /// Otherwise the same as `insert`. A rising share of buckets that were
+    /// already set shows the filter is saturating.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert_with_stats<T>(&mut self, e: &T) -> InsertStats
+    where
+        T: Hash,
+    {
+        let pair = self.hashers.hash_pair(e);
+
+        let mut already_set = 0;
+        for idx in self.hashers.pair_indexes(pair, self.buffer.len()) {
+            if self.buffer.get(idx).unwrap() {
+                already_set += 1;
+            }
+            self.buffer.set(idx, true);
+        }
+
+        let stats = InsertStats {
+            already_set,
+            n_hashers: self.n_hashers(),
+        };
+        if stats.is_new() {
+            self.size += 1;
+        }
+        self.log_insert(pair);
+
+        stats
+    }
+
+    /// Insert a member, unless the filter already holds as many elements as
+    /// it was built for
+    ///
+    /// Returns whether the member was definitely not present before, like
+    /// `insert`. Members that may already be present can still be inserted,
+    /// since that leaves the filter unchanged.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn try_insert<T>(&mut self, e: &T) -> Result<bool, SaturatedError>
+    where
+        T: Hash,
+    {
+        if self.size < self.capacity {
+            Ok(self.insert(e))
+        } else if self.may_contain(e) {
+            Ok(false)
+        } else {
+            Err(SaturatedError)
+        }
+    }
+
+    /// Insert a member passed by value, see `insert`
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert_val<T>(&mut self, e: T) -> bool
+    where
+        T: Hash + Copy,
+    {
+        self.insert(&e)
+    }
+
+    /// Check membership and insert a member, hashing it only once
+    ///
+    /// Returns whether the member was possibly present before, the opposite
+    /// of `insert`. Useful for deduplicating a stream.
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of and add
+    pub fn check_and_insert<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        !self.insert(e)
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.contains_indexes(self.indexes(e))
+    }
+
+    /// Confidence that an element is a member, between 0.0 and 1.0
+    ///
+    /// 0.0 if the element is definitely absent, otherwise one minus the
+    /// current false positive rate. This is a ranking signal rather than a
+    /// true probability, which would also depend on how often absent elements
+    /// are queried.
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn contains_probability<T>(&self, e: &T) -> f32
+    where
+        T: Hash,
+    {
+        if self.may_contain(e) {
+            1. - self.fp_rate()
+        } else {
+            0.
+        }
+    }
+
+    /// Check membership of a member made of several parts, see
+    /// `insert_parts`
+    ///
+    /// # Arguments
+    /// * `parts`: fields of the element to check membership of, in order
+    pub fn may_contain_parts(&self, parts: &[&dyn KeyPart]) -> bool {
+        self.may_contain(&Parts(parts))
+    }
+
+    /// Check membership of a byte string
+    ///
+    /// # Arguments
+    /// * `bytes`: element to check membership of
+    pub fn may_contain_bytes(&self, bytes: &[u8]) -> bool {
+        self.contains_indexes(self.hashers.indexes(bytes, self.buffer.len()))
+    }
+
+    /// Check membership by a hash that was already computed
+    ///
+    /// # Arguments
+    /// * `h`: 64-bit hash of the element, as given to `insert_hash`
+    pub fn may_contain_hash(&self, h: u64) -> bool {
+        self.contains_indexes(self.hashers.hash_indexes(h, self.buffer.len()))
+    }
+
+    /// Check membership of an element passed by value
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain_val<T>(&self, e: T) -> bool
+    where
+        T: Hash + Copy,
+    {
+        self.may_contain(&e)
+    }
+
+    /// Check membership of every element
+    ///
+    /// # Arguments
+    /// * `elems`: elements to check membership of
+    pub fn contains_all<'a, T, I>(&self, elems: I) -> bool
+    where
+        T: Hash + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        elems.into_iter().all(|e| self.may_contain(e))
+    }
+
+    /// Check membership of many elements at once
+    ///
+    /// Bit `i` of the result is whether `elems[i]` may be a member.
+    ///
+    /// # Arguments
+    /// * `elems`: elements to check membership of
+    pub fn may_contain_many<T>(&self, elems: &[T]) -> BitVec
+    where
+        T: Hash,
+    {
+        let mut found = BitVec::from_elem(elems.len(), false);
+        for (i, e) in elems.iter().enumerate() {
+            found.set(i, self.may_contain(e));
+        }
+        found
+    }
+
+    /// Remove every member, keeping the buffer and hashers
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.size = 0;
+        if let Some(ref mut log) = self.grow_log {
+            log.clear();
+        }
+    }
+
+    /// Union of two filters
+    ///
+    /// The result reports a member of either filter as possibly present. Its
+    /// size is the sum of both sizes, an upper bound on the true count.
+    ///
+    /// # Arguments
+    /// * `other`: filter to union with, must have the same buckets and hashers
+    pub fn union(
+        &self,
+        other: &BloomFilter<S>,
+    ) -> Result<BloomFilter<S>, MergeError> {
+        self.check_compatible(other)?;
+
+        let mut buffer = self.buffer.clone();
+        buffer.or(&other.buffer);
+
+        Ok(BloomFilter {
+            size: self.size + other.size,
+            buffer,
+            capacity: self.capacity,
+            design_fp_rate: self.design_fp_rate,
+            hashers: self.hashers.clone(),
+            grow_log: None,
+        })
+    }
+
+    /// Union another filter into this one
+    ///
+    /// Like `union`, but sets the other filter's buckets in this filter's
+    /// buffer rather than a copy of it. Nothing changes if the filters are
+    /// incompatible.
+    ///
+    /// # Arguments
+    /// * `other`: filter to union with, must have the same buckets and hashers
+    pub fn union_in_place(
+        &mut self,
+        other: &BloomFilter<S>,
+    ) -> Result<(), MergeError> {
+        self.check_compatible(other)?;
+
+        self.buffer.or(&other.buffer);
+        self.size += other.size;
+
+        match (&mut self.grow_log, &other.grow_log) {
+            (Some(log), Some(other_log)) => log.extend_from_slice(other_log),
+            (grow_log, _) => *grow_log = None,
+        }
+        if self.grow_log.is_some() && self.should_resize() {
+            self.grow();
+        }
+
+        Ok(())
+    }
+
+    /// Indexes of the buckets set in this filter but not in an earlier copy
+    /// of it, in ascending order
+    ///
+    /// Replaying them onto the copy with `apply_diff` brings its buckets up
+    /// to date, which for a filter that is only inserted into takes far less
+    /// than sending the whole buffer. Buckets cleared since the copy was
+    /// taken aren't included.
+    ///
+    /// # Arguments
+    /// * `previous`: earlier copy of this filter, must have the same buckets
+    ///   and hashers
+    pub fn diff(
+        &self,
+        previous: &BloomFilter<S>,
+    ) -> Result<Vec<usize>, MergeError> {
+        self.check_compatible(previous)?;
+
+        let mut indices = Vec::new();
+        let blocks = self.buffer.blocks().zip(previous.buffer.blocks());
+        for (i, (current, previous)) in blocks.enumerate() {
+            let mut new = current & !previous;
+            while new != 0 {
+                indices.push(
+                    i * u32::BITS as usize + new.trailing_zeros() as usize,
+                );
+                new &= new - 1;
+            }
+        }
+
+        Ok(indices)
+    }
+
+    /// Set the buckets at indexes from `diff`
+    ///
+    /// The size isn't replicated, since the diff doesn't say how many members
+    /// set the buckets, so it is left unchanged. See `estimated_cardinality`.
+    /// Neither are the members' hashes, so a filter that grows stops growing
+    /// rather than lose the replicated members when it next does.
+    ///
+    /// # Arguments
+    /// * `indices`: indexes of buckets to set
+    ///
+    /// # Panics
+    /// If an index isn't less than the number of buckets
+    pub fn apply_diff(&mut self, indices: &[usize]) {
+        for &idx in indices {
+            assert!(idx < self.buffer.len(), "bucket {} out of range", idx);
+            self.buffer.set(idx, true);
+        }
+
+        if !indices.is_empty() {
+            self.grow_log = None;
+        }
+    }
+
+    /// Union of many filters
+    ///
+    /// The filters are combined into the first one as they come, without
+    /// intermediate copies, stopping at the first one that is incompatible.
+    ///
+    /// # Arguments
+    /// * `filters`: filters to union, must all have the same buckets and
+    ///   hashers, and there must be at least one
+    pub fn union_all<I>(filters: I) -> Result<BloomFilter<S>, MergeError>
+    where
+        I: IntoIterator<Item = BloomFilter<S>>,
+    {
+        let mut filters = filters.into_iter();
+        let mut union = filters.next().ok_or(MergeError::NoFilters)?;
+
+        for filter in filters {
+            union.union_in_place(&filter)?;
+        }
+
+        Ok(union)
+    }
+
+    /// Intersection of two filters
+    ///
+    /// The result over-approximates the intersection: it reports every common
+    /// member as possibly present, along with some members of only one filter.
+    /// Its size is the smaller of both sizes, an upper bound on the true count.
+    ///
+    /// # Arguments
+    /// * `other`: filter to intersect with, must have the same buckets and
+    ///   hashers
+    pub fn intersection(
+        &self,
+        other: &BloomFilter<S>,
+    ) -> Result<BloomFilter<S>, MergeError> {
+        self.check_compatible(other)?;
+
+        let mut buffer = self.buffer.clone();
+        buffer.and(&other.buffer);
+
+        Ok(BloomFilter {
+            size: self.size.min(other.size),
+            buffer,
+            capacity: self.capacity,
+            design_fp_rate: self.design_fp_rate,
+            hashers: self.hashers.clone(),
+            grow_log: None,
+        })
+    }
+
+    /// Shrink a filter to fewer buckets, keeping its members
+    ///
+    /// Members aren't stored, so they can't be rehashed. Instead the buffer is
+    /// folded: with `f` current buckets per new bucket, bucket `i` of the
+    /// result is set if any of buckets `i * f .. (i + 1) * f` was. This is
+    /// only equivalent to having inserted every member into the smaller filter
+    /// when `new_buckets` divides the current bucket count, so any other size
+    /// is rejected. Filters built with differing sizes can then be merged by
+    /// resizing the larger one to the size of the smaller first.
+    ///
+    /// # Arguments
+    /// * `new_buckets`: number of buckets of the result, must evenly divide
+    ///   the current number of buckets
+    pub fn resize_to(
+        &self,
+        new_buckets: usize,
+    ) -> Result<BloomFilter<S>, MergeError> {
+        if new_buckets == 0 || !self.buckets().is_multiple_of(new_buckets) {
+            return Err(MergeError::IndivisibleBuckets);
+        }
+
+        let factor = self.buckets() / new_buckets;
+        let mut buffer = BitVec::from_elem(new_buckets, false);
+        for idx in self.set_bit_indices() {
+            buffer.set(self.hashers.fold(idx, factor, new_buckets), true);
+        }
+
+        Ok(BloomFilter {
+            size: self.size,
+            buffer,
+            capacity: self.capacity,
+            design_fp_rate: self.design_fp_rate,
+            hashers: self.hashers.clone(),
+            grow_log: None,
+        })
+    }
+
+    /// Shrink the filter to as few buckets as its members need
+    ///
+    /// The buffer is folded as by `resize_to`, so it can only shrink to a
+    /// number of buckets that divides the current one, and the number of
+    /// hashers is kept. The smallest such size whose false positive rate at
+    /// the current `size` is still within the designed rate is chosen, and
+    /// the filter is then considered built for `size` elements. Filters with
+    /// no smaller fitting size are left unchanged.
+    pub fn shrink_to_fit(&mut self) {
+        let buckets = self.buckets();
+        let fits = (2..=buckets)
+            .filter(|&factor| buckets.is_multiple_of(factor))
+            .map(|factor| buckets / factor)
+            .take_while(|&n_buckets| {
+                false_positive_rate(n_buckets, self.n_hashers(), self.size)
+                    <= self.design_fp_rate
+            })
+            .last();
+
+        if let Some(n_buckets) = fits {
+            let grow_log = self.grow_log.take();
+            *self = self.resize_to(n_buckets).unwrap();
+            self.capacity = self.size;
+            self.grow_log = grow_log;
+        }
+    }
+
+    /// Approximate number of distinct elements in the `BloomFilter`
+    ///
+    /// A member whose buckets were all set by earlier members isn't counted,
+    /// so this can undercount as the filter fills up.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of elements the filter was built for
+    ///
+    /// `try_insert` refuses new members once `size` reaches it.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether no elements have been inserted
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Number of buckets that a memebr can occupy
+    pub fn buckets(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Number of hashers being used
+    pub fn n_hashers(&self) -> usize {
+        self.hashers.len()
+    }
+
+    /// How indexes are derived from a member's hashes
+    pub fn index_strategy(&self) -> IndexStrategy {
+        self.hashers.strategy()
+    }
+
+    /// The salt hashed before every member, if there is one
+    pub fn salt(&self) -> Option<u64> {
+        self.hashers.salt()
+    }
+
+    /// Whether the filter grows once it holds too many elements
+    ///
+    /// Set with `BloomFilterBuilder::auto_grow`. Such a filter keeps the pair
+    /// of hashes of every insert, and whenever `should_resize` becomes true
+    /// it doubles its buckets and rehashes every member into them, until its
+    /// false positive rate is back within the designed rate. No member is
+    /// lost and the rate stays bounded, but:
+    ///
+    /// * the log takes 16 bytes per insert, many times the buffer itself
+    /// * growing takes time proportional to every insert so far, although
+    ///   since the buckets double this is constant per insert on average
+    /// * `union_in_place` with a filter that doesn't grow, `apply_diff`, and
+    ///   filters made by `union`, `intersection` or `resize_to`, don't grow
+    /// * neither `to_bytes` nor `compress` keeps the log, so decoded filters
+    ///   don't grow
+    pub fn auto_grow(&self) -> bool {
+        self.grow_log.is_some()
+    }
+
+    /// How this filter hashes members into buckets
+    ///
+    /// An empty filter that hashes the same way can be built from it with
+    /// `with_config`, even in another process.
+    pub fn hasher_config(&self) -> HasherConfig<S> {
+        HasherConfig {
+            states: self.hashers.states().clone(),
+            n_hashers: self.n_hashers(),
+            buckets: self.buckets(),
+            strategy: self.index_strategy(),
+            salt: self.salt(),
+        }
+    }
+
+    /// Number of buckets that are set
+    pub fn set_bits(&self) -> usize {
+        self.buffer.blocks().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Number of buckets expected to be set, given the size
+    ///
+    /// For `m` buckets, `k` hashers and `n` elements this is
+    /// `m * (1 - e^(-kn/m))`. A `set_bits` far from it means the members
+    /// aren't spread over the buckets the way independent hashers would
+    /// spread them, from a bug or from adversarial input.
+    pub fn expected_set_bits(&self) -> f32 {
+        let k = self.n_hashers() as f32;
+        let n = self.size as f32;
+        let m = self.buckets() as f32;
+
+        m * (1. - math::exp(-k * n / m))
+    }
+
+    /// The buffer of buckets, bucket `i` being bit `i`
+    pub fn as_bitvec(&self) -> &BitVec {
+        &self.buffer
+    }
+
+    /// Number of bytes of memory the filter uses
+    ///
+    /// The size of the filter itself, including its hashers, plus the bit
+    /// buffer it owns.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of::<BloomFilter<S>>()
+            + mem::size_of_val(self.buffer.storage())
+    }
+
+    /// Indexes of the buckets that are set, in ascending order
+    pub fn set_bit_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.buffer
+            .iter()
+            .enumerate()
+            .filter(|&(_, set)| set)
+            .map(|(idx, _)| idx)
+    }
+
+    /// Fraction of buckets that are set (0.0 -> 1.0)
+    pub fn load_factor(&self) -> f32 {
+        self.set_bits() as f32 / self.buckets() as f32
+    }
+
+    /// Fraction of buckets that are set (0.0 -> 1.0) in each of a number of
+    /// equal regions of the buffer, in order
+    ///
+    /// Good hashers fill every region evenly, so regions much fuller or
+    /// emptier than the `load_factor` point to clustering. Regions differ in
+    /// length by at most one bucket when `segments` doesn't divide the number
+    /// of buckets.
+    ///
+    /// # Arguments
+    /// * `segments`: number of regions, reduced to the number of buckets if
+    ///   larger
+    pub fn occupancy_map(&self, segments: usize) -> Vec<f32> {
+        let n_buckets = self.buckets();
+        let segments = segments.min(n_buckets);
+        let bound = |i: usize| {
+            (i as u128 * n_buckets as u128 / segments as u128) as usize
+        };
+
+        (0..segments)
+            .map(|i| {
+                let (start, end) = (bound(i), bound(i + 1));
+                let set = (start..end)
+                    .filter(|&idx| self.buffer.get(idx).unwrap())
+                    .count();
+                set as f32 / (end - start) as f32
+            })
+            .collect()
+    }
+
+    /// False positive rate
+    pub fn fp_rate(&self) -> f32 {
+        self.projected_fp_rate(self.size())
+    }
+
+    /// False positive rate the filter would have once it held a number of
+    /// elements
+    ///
+    /// # Arguments
+    /// * `n_elems`: number of elements
+    pub fn projected_fp_rate(&self, n_elems: usize) -> f32 {
+        false_positive_rate(self.buckets(), self.n_hashers(), n_elems)
+    }
+
+    /// Number of elements that can still be inserted before the false
+    /// positive rate exceeds a target
+    ///
+    /// Zero once the target has already been crossed.
+    ///
+    /// # Arguments
+    /// * `target_fp`: false positive rate to stay within
+    pub fn elements_until_fp(&self, target_fp: f32) -> usize {
+        max_elems_at_fp_rate(self.buckets(), self.n_hashers(), target_fp)
+            .saturating_sub(self.size())
+    }
+
+    /// False positive rate the filter was designed for
+    ///
+    /// This is the rate requested from `new_with_fp`. Filters given a number
+    /// of buckets instead are designed for the rate they reach once filled
+    /// with the expected number of elements.
+    pub fn design_fp_rate(&self) -> f32 {
+        self.design_fp_rate
+    }
+
+    /// Whether the filter holds so many elements that its false positive rate
+    /// exceeds the one it was designed for
+    ///
+    /// A trigger for rebuilding the filter with more buckets.
+    pub fn should_resize(&self) -> bool {
+        self.fp_rate() > self.design_fp_rate
+    }
+
+    /// Estimate the number of distinct elements from the bits that are set
+    ///
+    /// Unlike `size`, this doesn't depend on how many inserts were made. It
+    /// saturates at `usize::MAX` once every bit is set.
+    pub fn estimated_cardinality(&self) -> usize {
+        estimate_cardinality(self.buckets(), self.n_hashers(), self.set_bits())
+    }
+
+    /// Whether every bucket set in this filter is also set in another
+    ///
+    /// Always true when every member of this filter was also inserted into
+    /// the other, so false proves some member wasn't. True doesn't prove the
+    /// opposite, since the other filter's buckets may have been set by other
+    /// members.
+    ///
+    /// # Arguments
+    /// * `other`: filter to compare with, must have the same buckets and
+    ///   hashers
+    pub fn is_subset_of(
+        &self,
+        other: &BloomFilter<S>,
+    ) -> Result<bool, MergeError> {
+        self.check_compatible(other)?;
+
+        Ok(self
+            .buffer
+            .blocks()
+            .zip(other.buffer.blocks())
+            .all(|(a, b)| a & !b == 0))
+    }
+
+    /// Estimate the number of distinct elements inserted into either filter
+    ///
+    /// The same as the `estimated_cardinality` of their `union`, but counts
+    /// the bits set in either buffer without building it.
+    ///
+    /// # Arguments
+    /// * `other`: filter to combine with, must have the same buckets and
+    ///   hashers
+    pub fn estimated_union_cardinality(
+        &self,
+        other: &BloomFilter<S>,
+    ) -> Result<usize, MergeError> {
+        self.check_compatible(other)?;
+
+        let n_set = self
+            .buffer
+            .blocks()
+            .zip(other.buffer.blocks())
+            .map(|(a, b)| (a | b).count_ones() as usize)
+            .sum();
+
+        Ok(estimate_cardinality(
+            self.buckets(),
+            self.n_hashers(),
+            n_set,
+        ))
+    }
+
+    /// Estimate the number of distinct elements inserted into exactly one of
+    /// two filters
+    ///
+    /// Members of only one filter whose buckets happen to be shared with
+    /// members of both leave no trace in the XOR of the buffers, so counting
+    /// its set bits underestimates. Instead, as for `estimated_jaccard`, the
+    /// size of the intersection follows by inclusion-exclusion from the
+    /// estimated sizes of both sets and their union, and is subtracted from
+    /// the union.
+    ///
+    /// # Arguments
+    /// * `other`: filter to compare with, must have the same buckets and
+    ///   hashers
+    pub fn estimated_symmetric_difference(
+        &self,
+        other: &BloomFilter<S>,
+    ) -> Result<usize, MergeError> {
+        let union = self.estimated_union_cardinality(other)?;
+        let a = self.estimated_cardinality();
+        let b = other.estimated_cardinality();
+
+        Ok(union.saturating_mul(2).saturating_sub(a.saturating_add(b)))
+    }
+
+    /// Estimate the Jaccard index of the sets inserted into two filters
+    ///
+    /// The sizes of both sets and of their union are estimated from the bits
+    /// set in each buffer and in their union, and the size of the
+    /// intersection follows by inclusion-exclusion. Two empty filters are
+    /// considered identical.
+    ///
+    /// # Arguments
+    /// * `other`: filter to compare with, must have the same buckets and
+    ///   hashers
+    pub fn estimated_jaccard(
+        &self,
+        other: &BloomFilter<S>,
+    ) -> Result<f32, MergeError> {
+        let union = self.estimated_union_cardinality(other)? as f32;
+        if union == 0. {
+            return Ok(1.);
+        }
+
+        let a = self.estimated_cardinality() as f32;
+        let b = other.estimated_cardinality() as f32;
+        let intersection = (a + b - union).max(0.);
+
+        Ok((intersection / union).min(1.))
+    }
+
+    /// How independently the hashers place members, between 0.0 and 1.0
+    ///
+    /// The share of pairs of a member's indexes that fall in different
+    /// buckets, over every member of `sample`, which doesn't need to have
+    /// been inserted. Independent hashers score about `1 - 1 / buckets`, and
+    /// hashers that put every member's indexes in one bucket score 0, which
+    /// would make the filter as weak as one with a single hasher. Filters
+    /// with fewer than two hashers, or an empty sample, score 1.
+    ///
+    /// # Arguments
+    /// * `sample`: elements to hash
+    pub fn hasher_independence_score<T>(&self, sample: &[T]) -> f32
+    where
+        T: Hash,
+    {
+        let n_hashers = self.n_hashers();
+        let n_pairs = n_hashers * n_hashers.saturating_sub(1) / 2;
+        if n_pairs == 0 || sample.is_empty() {
+            return 1.;
+        }
+
+        let mut indexes = Vec::with_capacity(n_hashers);
+        let mut collisions = 0;
+        for e in sample {
+            indexes.clear();
+            indexes.extend(self.indexes(e));
+            for (i, a) in indexes.iter().enumerate() {
+                collisions +=
+                    indexes[i + 1..].iter().filter(|&b| a == b).count();
+            }
+        }
+
+        1. - collisions as f32 / (n_pairs * sample.len()) as f32
+    }
+
+    /// Whether two filters are probably identical, comparing a sample of
+    /// their buckets
+    ///
+    /// Much cheaper than `==` for large filters when `samples` is small.
+    /// False proves the filters differ, but true only means that every
+    /// sampled bucket matched. The buckets are spread pseudo-randomly over
+    /// the buffer, but are the same on every call, so repeating a comparison
+    /// doesn't make it more reliable.
+    ///
+    /// # Arguments
+    /// * `other`: filter to compare with, must have the same buckets and
+    ///   hashers
+    /// * `samples`: number of buckets to compare
+    pub fn approx_eq(
+        &self,
+        other: &BloomFilter<S>,
+        samples: usize,
+    ) -> Result<bool, MergeError> {
+        self.check_compatible(other)?;
+
+        let n_buckets = self.buckets();
+        Ok((1..=samples as u64).all(|i| {
+            let idx = reduce(mix(i), n_buckets);
+            self.buffer.get(idx) == other.buffer.get(idx)
+        }))
+    }
+
+    /// The indexes that a element hashes to
+    fn indexes<T>(&self, e: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        self.hashers.indexes(e, self.buffer.len())
+    }
+
+    /// Set the buckets of a member from its pair of hashes
+    fn insert_pair(&mut self, pair: [u64; 2]) -> bool {
+        let indexes = self.hashers.pair_indexes(pair, self.buffer.len());
+        let is_new = self.insert_indexes(indexes);
+        self.log_insert(pair);
+        is_new
+    }
+
+    /// Record an inserted member's hashes if the filter grows automatically,
+    /// growing it if it's now too full
+    fn log_insert(&mut self, pair: [u64; 2]) {
+        match self.grow_log {
+            Some(ref mut log) => log.push(pair),
+            None => return,
+        }
+
+        if self.should_resize() {
+            self.grow();
+        }
+    }
+
+    /// Double the buckets until the false positive rate is back within the
+    /// designed rate, rehashing every logged member
+    fn grow(&mut self) {
+        let mut log = self.grow_log.take().unwrap_or_default();
+        log.sort_unstable();
+        log.dedup();
+
+        let mut n_buckets = self.buffer.len();
+        let mut factor = 1;
+        while false_positive_rate(n_buckets, self.n_hashers(), self.size)
+            > self.design_fp_rate
+        {
+            n_buckets *= 2;
+            factor *= 2;
+        }
+
+        self.buffer = BitVec::from_elem(n_buckets, false);
+        for &pair in &log {
+            for idx in self.hashers.pair_indexes(pair, n_buckets) {
+                self.buffer.set(idx, true);
+            }
+        }
+
+        self.capacity *= factor;
+        self.grow_log = Some(log);
+    }
+
+    /// Set the buckets at the indexes of a member
+    fn insert_indexes<I>(&mut self, indexes: I) -> bool
+    where
+        I: Iterator<Item = usize>,
+    {
+        let is_new = set_indexes(&mut self.buffer, indexes);
+        if is_new {
+            self.size += 1;
+        }
+
+        is_new
+    }
+
+    /// Whether the buckets at the indexes of a member are all set
+    fn contains_indexes<I>(&self, indexes: I) -> bool
+    where
+        I: Iterator<Item = usize>,
+    {
+        indexes_are_set(&self.buffer, indexes)
+    }
+
+    /// Check that another filter maps members to the same buckets as this one
+    fn check_compatible(
+        &self,
+        other: &BloomFilter<S>,
+    ) -> Result<(), MergeError> {
+        if self.buckets() != other.buckets() {
+            return Err(MergeError::BucketMismatch);
+        }
+
+        if self.hashers.same_as(&other.hashers) {
+            Ok(())
+        } else {
+            Err(MergeError::HasherMismatch)
+        }
+    }
+}
+
+/// Filters are equal when they have the same buckets set and hash members
+/// the same way
+///
+/// Hashers are compared like `union` does, by a fingerprint of their output.
+/// The approximate sizes aren't compared: filters built by inserting the same
+/// members in a different order can disagree on them.
+impl<S> PartialEq for BloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    fn eq(&self, other: &BloomFilter<S>) -> bool {
+        self.check_compatible(other).is_ok() && self.buffer == other.buffer
+    }
+}
+
+impl<S> Eq for BloomFilter<S> where S: BuildHasher + Clone {}
+
+/// Consistent with `PartialEq`: hashes the hashers and the buffer, but not
+/// the approximate size
+impl<S> Hash for BloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        self.hashers.hash(state);
+        self.buffer.hash(state);
+    }
+}
+
+/// A one line summary of the filter's statistics
+impl<S> fmt::Display for BloomFilter<S>
+where
+    S: BuildHasher + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "BloomFilter {{ size: {}, buckets: {}, hashers: {}, set bits: {}, \
+             load factor: {:.3}, fp rate: {:.6} }}",
+            self.size(),
+            self.buckets(),
+            self.n_hashers(),
+            self.set_bits(),
+            self.load_factor(),
+            self.fp_rate()
+        )
+    }
+}
+
+impl<S, T> Extend<T> for BloomFilter<S>
+where
+    S: BuildHasher + Clone,
+    T: Hash,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for e in iter {
+            self.insert(&e);
+        }
+    }
+}
+
+/// Builds a filter sized for exactly the collected elements, with a false
+/// positive rate of `DEFAULT_FP_RATE`
+///
+/// See `BloomFilter::from_exact`.
+impl<T> FromIterator<T> for BloomFilter
+where
+    T: Hash,
+{
+    fn from_iter<I>(iter: I) -> BloomFilter
+    where
+        I: IntoIterator<Item = T>,
+    {
+        BloomFilter::from_exact(iter, DEFAULT_FP_RATE)
+    }
+}
+
+/// Builds a filter for `DEFAULT_CAPACITY` elements with a false positive rate
+/// of `DEFAULT_FP_RATE`
+impl Default for BloomFilter {
+    fn default() -> BloomFilter {
+        BloomFilter::new_with_fp(DEFAULT_CAPACITY, DEFAULT_FP_RATE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::string::ToString;
+
+    /// Hashes a `u64` to itself plus an offset
+    #[derive(Clone)]
+    pub(crate) struct OffsetState(pub(crate) u64);
+
+    pub(crate) struct OffsetHasher(u64);
+
+    impl BuildHasher for OffsetState {
+        type Hasher = OffsetHasher;
+
+        fn build_hasher(&self) -> OffsetHasher {
+            OffsetHasher(self.0)
+        }
+    }
+
+    impl Hasher for OffsetHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &b in bytes {
+                self.0 = self.0.wrapping_add(b as u64);
+            }
+        }
+
+        fn write_u64(&mut self, n: u64) {
+            self.0 = self.0.wrapping_add(n);
+        }
+    }
+
+    /// Hashes everything to the same value, whatever it's given
+    #[derive(Clone)]
+    struct FixedState(u64);
+
+    struct FixedHasher(u64);
+
+    impl BuildHasher for FixedState {
+        type Hasher = FixedHasher;
+
+        fn build_hasher(&self) -> FixedHasher {
+            FixedHasher(self.0)
+        }
+    }
+
+    impl Hasher for FixedHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    /// Test that the bloom filter will always return the same results
+    #[test]
+    fn test_is_deterministic() {
+        let to_add = "do add this";
+        let dont_add = 123;
+        let mut filter = BloomFilter::new_with_size(1, 100);
+        filter.insert(&to_add);
+
+        // Check membership twice to make sure that the results are reproducable
+        // even though the hashers are being reset
+        assert!(filter.may_contain(&to_add));
+        assert!(filter.may_contain(&to_add));
+
+        assert!(!filter.may_contain(&dont_add));
+        assert!(!filter.may_contain(&dont_add));
+    }
+
+    /// Test that any element that implements the `Hash` trait can be added to
+    /// the `BloomFilter`
+    #[test]
+    fn test_is_heterogenous() {
+        let a = "do add this";
+        let b = 1234567;
+
+        let mut filter = BloomFilter::new_with_fp(2, 0.01);
+        filter.insert(&a);
+        filter.insert(&b);
+
+        assert!(filter.may_contain(&a));
+        assert!(filter.may_contain(&a));
+        assert!(filter.may_contain(&b));
+        assert!(filter.may_contain(&b));
+    }
+
+    /// Test that a member can be told apart from non-members that share only
+    /// some of its buckets
+    #[test]
+    fn test_partial_overlap_is_absent() {
+        let mut filter = BloomFilter::new_with_size(1, 100);
+        filter.insert(&"do add this");
+
+        let idxs: Vec<_> = filter.indexes(&"do add this").collect();
+        for idx in idxs.iter().skip(1) {
+            filter.buffer.set(*idx, false);
+        }
+        assert!(!filter.may_contain(&"do add this"));
+
+        filter.buffer.set(idxs[1], true);
+        assert!(!filter.may_contain(&"do add this"));
+
+        for idx in idxs {
+            filter.buffer.set(idx, true);
+        }
+        assert!(filter.may_contain(&"do add this"));
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let inserted: Vec<u64> = (0..100_000).map(|e| e * 7919).collect();
+        let filter = BloomFilter::from_exact(&inserted, 0.01);
+
+        testing::assert_no_false_negatives(&filter, &inserted);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_no_false_negatives_catches_missing() {
+        let filter = BloomFilter::new_with_fp(10, 0.01);
+
+        testing::assert_no_false_negatives(&filter, &["never added"]);
+    }
+
+    #[test]
+    fn test_new_pow2_with_fp() {
+        let inserted: Vec<u32> = (0..1000).collect();
+        let mut filter = BloomFilter::new_pow2_with_fp(1000, 0.01);
+        filter.extend(&inserted);
+
+        assert!(filter.buckets().is_power_of_two());
+        assert!(
+            filter.buckets() >= BloomFilter::new_with_fp(1000, 0.01).buckets()
+        );
+        assert_eq!(IndexStrategy::BitMask, filter.index_strategy());
+        testing::assert_no_false_negatives(&filter, &inserted);
+
+        let false_positives =
+            (1000..11_000).filter(|e| filter.may_contain(e)).count();
+        assert!(false_positives < 150);
+    }
+
+    #[test]
+    fn test_strategies_have_no_false_negatives() {
+        for &strategy in &[
+            IndexStrategy::Modulo,
+            IndexStrategy::MultiplyShift,
+            IndexStrategy::DoubleHash,
+            IndexStrategy::BitMask,
+        ] {
+            let inserted: Vec<u32> = (0..10_000).collect();
+            let mut filter =
+                BloomFilter::new_with_strategy(10_000, 0.01, strategy);
+            filter.extend(&inserted);
+
+            assert_eq!(strategy, filter.index_strategy());
+            testing::assert_no_false_negatives(&filter, &inserted);
+        }
+    }
+
+    #[test]
+    fn test_extend() {
+        let elems = vec![1, 2, 3, 4, 5];
+
+        let mut filter = BloomFilter::new_with_fp(elems.len(), 0.01);
+        filter.extend(elems.clone());
+
+        for e in &elems {
+            assert!(filter.may_contain(e));
+        }
+    }
+
+    #[test]
+    fn test_contains_all() {
+        let mut filter = BloomFilter::new_with_size(3, 100);
+        filter.extend(vec![1, 2, 3]);
+
+        assert!(filter.contains_all(&[1, 2, 3]));
+        assert!(filter.contains_all(&[0; 0]));
+        assert!(!filter.contains_all(&[1, 2, 4]));
+    }
+
+    #[test]
+    fn test_collect() {
+        let filter: BloomFilter = (0..100).collect();
+
+        assert!((0..100).all(|e| filter.may_contain(&e)));
+
+        let false_positives =
+            (100..1100).filter(|e| filter.may_contain(e)).count();
+        assert!(false_positives < 50);
+    }
+
+    #[test]
+    fn test_from_exact() {
+        let filter = BloomFilter::from_exact(0..10_000, 0.01);
+        assert_eq!(10_000, filter.capacity());
+        assert!((0..10_000).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_from_exact_with_hasher() {
+        let mut keys = [(1, 2), (3, 4)].iter();
+        let filter =
+            BloomFilter::from_exact_with_hasher(0..10_000, 0.01, || {
+                let &(k0, k1) = keys.next().unwrap();
+                SipState::with_keys(k0, k1)
+            });
+        assert!((0..10_000).all(|e| filter.may_contain(&e)));
+
+        let false_positives =
+            (10_000..110_000).filter(|e| filter.may_contain(e)).count();
+        // the hashers are fixed, so this is reproducible
+        let observed = false_positives as f32 / 100_000.;
+        assert!((observed - 0.01).abs() < 0.001);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_reader() {
+        let lines = "alpha\nbeta\r\ngamma\n\ndelta";
+        let reader = std::io::Cursor::new(lines);
+        let filter = BloomFilter::from_reader(reader, 10, 0.01).unwrap();
+
+        for line in &["alpha", "beta", "gamma", "", "delta"] {
+            assert!(filter.may_contain(line));
+        }
+        assert!(filter.may_contain(&"delta".to_string()));
+        assert!(!filter.may_contain(&"alpha\n"));
+        assert_eq!(5, filter.size());
+    }
+
+    #[test]
+    fn test_new_with_memory() {
+        for &max_bytes in &[1024, 1027, 2 * 1024 * 1024] {
+            let filter = BloomFilter::new_with_memory(1000, max_bytes);
+            assert!(filter.memory_bytes() <= max_bytes);
+            assert!(filter.memory_bytes() > max_bytes - 8);
+        }
+
+        let mut filter = BloomFilter::new_with_memory(1000, 2 * 1024 * 1024);
+        filter.extend(0..1000);
+        assert!((0..1000).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_new_with_size_and_hashers() {
+        let mut filter = BloomFilter::new_with_size_and_hashers(1000, 3);
+        assert_eq!(3, filter.n_hashers());
+        assert_eq!(1000, filter.buckets());
+
+        filter.extend(0..100);
+        assert!((0..100).all(|e| filter.may_contain(&e)));
+        assert!(filter.design_fp_rate() > 0.);
+    }
+
+    #[test]
+    fn test_new_with_size_and_many_hashers() {
+        let mut filter = BloomFilter::new_with_size_and_hashers(10, 20);
+        assert_eq!(1, filter.capacity());
+        assert_eq!(Ok(true), filter.try_insert(&"do add this"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_size_and_no_hashers() {
+        BloomFilter::new_with_size_and_hashers(1000, 0);
+    }
+
+    #[test]
+    fn test_new_with_salt() {
+        let seeds = [(1, 2), (3, 4)];
+        let unsalted = BloomFilter::new_with_seeds(10, 1000, seeds);
+        let salted = |salt| BloomFilter {
+            hashers: unsalted.hashers.clone().with_salt(salt),
+            ..unsalted.clone()
+        };
+        let (a, also_a, b) = (salted(1), salted(1), salted(2));
+        assert_eq!(Some(1), a.salt());
+        assert_eq!(None, unsalted.salt());
+
+        for e in 0..100 {
+            assert!(a.indexes(&e).eq(also_a.indexes(&e)));
+            assert!(!a.indexes(&e).eq(b.indexes(&e)));
+            assert!(!a.indexes(&e).eq(unsalted.indexes(&e)));
+        }
+        assert_eq!(Err(MergeError::HasherMismatch), a.union(&b).map(|_| ()));
+
+        let mut filter = BloomFilter::new_with_salt(100, 0.01, 42);
+        filter.extend(0..100);
+        assert_eq!(Some(42), filter.salt());
+        assert!((0..100).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_new_portable() {
+        let mut filter = BloomFilter::new_portable(1000, 0.01);
+        assert_eq!(9586, filter.buckets());
+        assert_eq!(7, filter.n_hashers());
+
+        // pinned, so that any change to the hashing shows up here
+        let indexes: Vec<usize> = filter.indexes(&"portable").collect();
+        assert_eq!(vec![5732, 4289, 3013, 5353, 1196, 3388, 3152], indexes);
+        let indexes: Vec<usize> = filter.indexes(&42u32).collect();
+        assert_eq!(vec![6915, 4701, 4387, 1235, 87, 7856, 3985], indexes);
+
+        filter.extend(0..1000u64);
+        assert!((0..1000u64).all(|e| filter.may_contain(&e)));
+        assert_eq!(filter, {
+            let mut other = BloomFilter::new_portable(1000, 0.01);
+            other.extend((0..1000u64).rev());
+            other
+        });
+    }
+
+    #[test]
+    fn test_default() {
+        let mut filter = BloomFilter::default();
+        assert!(filter.buckets() > 0);
+        assert!(filter.n_hashers() >= 1);
+        assert_eq!(DEFAULT_FP_RATE, filter.design_fp_rate());
+
+        filter.extend(&["a", "b", "c", "d", "e"]);
+        assert!(filter.contains_all(&["a", "b", "c", "d", "e"]));
+        assert!(!filter.may_contain(&"z"));
+    }
+
+    #[test]
+    fn test_new_filter_contains_nothing() {
+        let filter = BloomFilter::new_with_fp(100, 0.01);
+        assert!(!filter.may_contain(&"never added"));
+        assert!(!filter.may_contain_hash(42));
+
+        let no_hashers = BloomFilter {
+            hashers: Hashers::new(0, SipState::new),
+            ..filter
+        };
+        assert!(!no_hashers.may_contain(&"never added"));
+        assert!(!no_hashers.may_contain_hash(42));
+    }
+
+    #[test]
+    fn test_undersized_has_a_hasher() {
+        let mut filter = BloomFilter::new_with_size(1_000_000, 10);
+        assert_eq!(1, filter.n_hashers());
+        assert!(!filter.may_contain(&"never added"));
+
+        filter.insert(&"do add this");
+        assert_eq!(1, filter.set_bits());
+    }
+
+    #[test]
+    fn test_collect_empty() {
+        let filter: BloomFilter = Vec::<u32>::new().into_iter().collect();
+
+        assert_eq!(1, filter.n_hashers());
+        assert!(!filter.may_contain(&0));
+    }
+
+    #[test]
+    fn test_estimated_cardinality() {
+        let mut filter = BloomFilter::new_with_fp(1000, 0.01);
+        filter.extend(0..1000);
+
+        let estimate = filter.estimated_cardinality() as f32;
+        assert!((estimate - 1000.).abs() < 50.);
+    }
+
+    #[test]
+    fn test_estimated_cardinality_ignores_duplicates() {
+        let mut filter = BloomFilter::new_with_fp(1000, 0.01);
+        for _ in 0..10 {
+            filter.extend(0..100);
+        }
+
+        let estimate = filter.estimated_cardinality() as f32;
+        assert!((estimate - 100.).abs() < 10.);
+    }
+
+    #[test]
+    fn test_estimated_cardinality_empty() {
+        let filter = BloomFilter::new_with_fp(1000, 0.01);
+        assert_eq!(0, filter.estimated_cardinality());
+    }
+
+    #[test]
+    fn test_set_bits() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        assert_eq!(0, filter.set_bits());
+        assert_eq!(0., filter.load_factor());
+
+        filter.insert(&"do add this");
+        let set_bits = filter.set_bits();
+        assert!(set_bits > 0 && set_bits <= filter.n_hashers());
+
+        filter.extend(0..1000);
+        assert!(filter.set_bits() > set_bits);
+        assert!(filter.load_factor() > 0. && filter.load_factor() <= 1.);
+    }
+
+    #[test]
+    fn test_as_bitvec() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        assert_eq!(filter.buckets(), filter.as_bitvec().len());
+        assert!(filter.as_bitvec().none());
+
+        filter.insert(&"do add this");
+        let bits = filter.as_bitvec();
+        assert!(filter.indexes(&"do add this").all(|idx| bits[idx]));
+        assert_eq!(filter.set_bits(), bits.iter().filter(|&b| b).count());
+    }
+
+    #[test]
+    fn test_memory_bytes() {
+        let small = BloomFilter::new_with_size(10, 8000);
+        let large = BloomFilter::new_with_size(10, 80_000);
+
+        assert!(small.memory_bytes() > 8000 / 8);
+        assert_eq!(72_000 / 8, large.memory_bytes() - small.memory_bytes());
+    }
+
+    #[test]
+    fn test_set_bit_indices() {
+        let mut filter = BloomFilter::new_with_size(10, 1000);
+        assert_eq!(0, filter.set_bit_indices().count());
+
+        filter.insert(&"do add this");
+        let mut idxs: Vec<_> = filter.indexes(&"do add this").collect();
+        idxs.sort();
+        idxs.dedup();
+
+        assert!(filter.set_bit_indices().eq(idxs));
+    }
+
+    #[test]
+    fn test_load_factor_full() {
+        let mut filter = BloomFilter::new_with_size(10, 10);
+        filter.extend(0..1000);
+
+        assert_eq!(10, filter.set_bits());
+        assert_eq!(1., filter.load_factor());
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let mut filter = BloomFilter::new_with_size(1, 100);
+        assert!(filter.is_empty());
+
+        filter.insert(&"do add this");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_size_increments() {
+        let mut filter = BloomFilter::new_with_size(3, 100);
+        filter.insert(&"do add this");
+        filter.insert(&"and this");
+        filter.insert(&"and this too");
+
+        assert_eq!(3, filter.size());
+    }
+
+    #[test]
+    fn test_insert_reports_new() {
+        let mut filter = BloomFilter::new_with_size(1, 100);
+
+        assert!(filter.insert(&"do add this"));
+        assert!(!filter.insert(&"do add this"));
+    }
+
+    #[test]
+    fn test_capacity() {
+        assert_eq!(1234, BloomFilter::new_with_fp(1234, 0.01).capacity());
+        assert_eq!(10, BloomFilter::new_with_size(10, 100).capacity());
+    }
+
+    #[test]
+    fn test_try_insert() {
+        let mut filter =
+            BloomFilter::new_with_seeds(100, 1000, [(1, 2), (3, 4)]);
+        for e in 0..100 {
+            assert_eq!(Ok(true), filter.try_insert(&e));
+        }
+
+        assert_eq!(100, filter.size());
+        assert_eq!(Err(SaturatedError), filter.try_insert(&100));
+        assert_eq!(Ok(false), filter.try_insert(&0));
+        assert!(!filter.may_contain(&100));
+    }
+
+    #[test]
+    fn test_reprovision() {
+        let mut filter = BloomFilter::new_with_fp(10, 0.1);
+        filter.extend(0..10);
+
+        for &(n_elems, fp_rate) in &[(1000, 0.001), (5, 0.5)] {
+            filter.reprovision(n_elems, fp_rate);
+            let expected = BloomFilter::new_with_fp(n_elems, fp_rate);
+
+            assert_eq!(expected.buckets(), filter.buckets());
+            assert_eq!(expected.n_hashers(), filter.n_hashers());
+            assert_eq!(fp_rate, filter.design_fp_rate());
+            assert!(filter.is_empty());
+            assert_eq!(0, filter.set_bits());
+        }
+    }
+
+    #[test]
+    fn test_reprovision_keeps_salt_and_strategy() {
+        let mut filter = BloomFilter::new_pow2_with_fp(10, 0.1);
+        filter.hashers = filter.hashers.with_salt(42);
+        let mut peer = filter.clone();
+
+        filter.reprovision(1000, 0.001);
+        peer.reprovision(1000, 0.001);
+
+        assert_eq!(Some(42), filter.salt());
+        assert_eq!(IndexStrategy::BitMask, filter.index_strategy());
+        assert!(filter.buckets().is_power_of_two());
+        assert_eq!(
+            optimal_n_hashers(filter.buckets(), 1000),
+            filter.n_hashers()
+        );
+
+        filter.insert(&"do add this");
+        peer.insert(&"do add this");
+        assert_eq!(filter, peer);
+    }
+
+    #[test]
+    fn test_check_and_insert() {
+        let mut filter = BloomFilter::new_with_size(1, 100);
+
+        assert!(!filter.check_and_insert(&"do add this"));
+        assert!(filter.check_and_insert(&"do add this"));
+        assert!(filter.may_contain(&"do add this"));
+    }
+
+    #[test]
+    fn test_may_contain_many() {
+        let mut filter =
+            BloomFilter::new_with_seeds(10, 1000, [(1, 2), (3, 4)]);
+        filter.insert(&"b");
+        filter.insert(&"d");
+
+        let found = filter.may_contain_many(&["a", "b", "c", "d"]);
+        assert!(found.eq_vec(&[false, true, false, true]));
+        assert!(filter.may_contain_many::<u32>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_insert_bytes() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        filter.insert_bytes(b"raw bytes");
+        filter.insert(&&b"generic bytes"[..]);
+
+        assert!(filter.may_contain_bytes(b"raw bytes"));
+        assert!(filter.may_contain(&&b"raw bytes"[..]));
+        assert!(filter.may_contain_bytes(b"generic bytes"));
+        assert!(!filter.may_contain_bytes(b"never added"));
+
+        let mut generic = empty_copy(&filter);
+        generic.insert(&&b"raw bytes"[..]);
+        generic.insert(&&b"generic bytes"[..]);
+        assert_eq!(filter, generic);
+    }
+
+    #[test]
+    fn test_insert_hash() {
+        let keys = SipState::with_keys(5, 6);
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        for e in 0..100u32 {
+            filter.insert_hash(keys.hash_one(e));
+        }
+
+        assert!((0..100u32).all(|e| filter.may_contain_hash(keys.hash_one(e))));
+
+        let false_positives = (100..10_100u32)
+            .filter(|&e| filter.may_contain_hash(keys.hash_one(e)))
+            .count();
+        assert!(false_positives < 150);
+    }
+
+    #[test]
+    fn test_insert_with_stats() {
+        let mut filter = BloomFilter::new_with_size(10, 1000);
+
+        let first = filter.insert_with_stats(&"do add this");
+        assert_eq!(filter.n_hashers(), first.n_hashers);
+        assert!(first.is_new());
+
+        let second = filter.insert_with_stats(&"do add this");
+        assert_eq!(filter.n_hashers(), second.already_set);
+        assert!(!second.is_new());
+        assert_eq!(1, filter.size());
+    }
+
+    #[test]
+    fn test_by_value_matches_by_reference() {
+        let mut by_val =
+            BloomFilter::new_with_seeds(100, 1000, [(1, 2), (3, 4)]);
+        let mut by_ref = by_val.clone();
+
+        for e in 0..100u32 {
+            assert_eq!(by_ref.insert(&e), by_val.insert_val(e));
+        }
+
+        assert_eq!(by_ref, by_val);
+        for e in 0..10_000u32 {
+            assert_eq!(by_ref.may_contain(&e), by_val.may_contain_val(e));
+        }
+    }
+
+    #[test]
+    fn test_size_ignores_duplicates() {
+        let to_add = "do add this";
+
+        let mut filter = BloomFilter::new_with_size(3, 100);
+        filter.insert(&to_add);
+        filter.insert(&to_add);
+        filter.insert(&to_add);
+
+        assert_eq!(1, filter.size());
+    }
+
+    #[test]
+    fn test_design_fp_rate_with_fp() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        assert_eq!(0.01, filter.design_fp_rate());
+
+        filter.extend(0..1000);
+        assert_eq!(0.01, filter.design_fp_rate());
+    }
+
+    #[test]
+    fn test_design_fp_rate_with_size() {
+        let filter = BloomFilter::new_with_size(100, 1000);
+        let k = filter.n_hashers();
+
+        assert_eq!(false_positive_rate(1000, k, 100), filter.design_fp_rate());
+    }
+
+    #[test]
+    fn test_should_resize() {
+        let mut filter = BloomFilter::new_with_fp(100, 0.01);
+        assert!(!filter.should_resize());
+
+        let mut e = 0;
+        while !filter.should_resize() {
+            filter.insert(&e);
+            e += 1;
+        }
+
+        assert!(filter.size() >= 90);
+        assert!(filter.fp_rate() > 0.01);
+    }
+
+    #[test]
+    fn test_fp_rate_is_zero_no_elems() {
+        let filter = BloomFilter::new_with_size(100, 100);
+        assert_eq!(0.0, filter.fp_rate());
+    }
+
+    #[test]
+    fn test_projected_fp_rate() {
+        let mut filter = BloomFilter::new_with_fp(1000, 0.01);
+        assert_eq!(0., filter.projected_fp_rate(0));
+        assert!((filter.projected_fp_rate(1000) - 0.01).abs() < 0.001);
+
+        let rates: Vec<f32> = (1..=50)
+            .map(|n| filter.projected_fp_rate(n * 100))
+            .collect();
+        assert!(rates.windows(2).all(|w| w[0] < w[1]));
+
+        filter.extend(0..100);
+        assert_eq!(filter.fp_rate(), filter.projected_fp_rate(filter.size()));
+    }
+
+    #[test]
+    fn test_elements_until_fp() {
+        let mut filter = BloomFilter::new_with_fp(1000, 0.01);
+        let mut headroom = filter.elements_until_fp(0.01);
+        assert!(headroom > 950 && headroom < 1050);
+
+        let mut e = 0;
+        while headroom > 0 {
+            filter.extend(e..e + 100);
+            e += 100;
+
+            let next = filter.elements_until_fp(0.01);
+            assert!(next < headroom);
+            headroom = next;
+        }
+
+        assert!(filter.projected_fp_rate(filter.size() + 2) > 0.01);
+        assert_eq!(0, filter.elements_until_fp(0.01));
+    }
+
+    #[test]
+    fn test_with_config() {
+        let mut filter = BloomFilter::new_with_salt(100, 0.01, 7);
+        filter.insert(&"shared");
+
+        let config = filter.hasher_config();
+        assert_eq!(Some(7), config.salt);
+        assert_eq!(filter.buckets(), config.buckets);
+
+        let mut copy = BloomFilter::with_config(config);
+        assert!(copy.is_empty());
+        assert_eq!(
+            filter.indexes(&"shared").collect::<Vec<_>>(),
+            copy.indexes(&"shared").collect::<Vec<_>>()
+        );
+
+        copy.insert(&"shared");
+        assert_eq!(filter.as_bitvec(), copy.as_bitvec());
+        assert!(filter.union(&copy).is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_with_config_bit_mask_needs_pow2() {
+        let mut config =
+            BloomFilter::new_pow2_with_fp(100, 0.01).hasher_config();
+        config.buckets += 1;
+        BloomFilter::with_config(config);
+    }
+
+    #[test]
+    fn test_contains_probability() {
+        let mut filter =
+            BloomFilter::new_with_seeds(100, 1000, [(1, 2), (3, 4)]);
+        filter.extend(0..100);
+
+        let present = filter.contains_probability(&0);
+        assert_eq!(1. - filter.fp_rate(), present);
+        assert!(present > 0.95);
+
+        let absent = (100..200).find(|e| !filter.may_contain(e)).unwrap();
+        assert_eq!(0., filter.contains_probability(&absent));
+    }
+
+    fn auto_growing(n_elems: usize, fp_rate: f32) -> BloomFilter {
+        BloomFilterBuilder::new()
+            .expected_elements(n_elems)
+            .false_positive_rate(fp_rate)
+            .auto_grow(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_auto_grow() {
+        let mut filter = auto_growing(100, 0.01);
+        let initial_buckets = filter.buckets();
+
+        let members: Vec<u32> = (0..10_000).collect();
+        filter.extend(&members);
+
+        assert!(filter.buckets() >= 64 * initial_buckets);
+        assert!(!filter.should_resize());
+        testing::assert_no_false_negatives(&filter, &members);
+
+        let fps = (10_000..20_000).filter(|e| filter.may_contain(e)).count();
+        assert!(fps < 200, "{} false positives", fps);
+    }
+
+    #[test]
+    fn test_auto_grow_stops_after_foreign_union() {
+        let mut filter = auto_growing(100, 0.01);
+        let mut other = empty_copy(&filter);
+        assert!(!other.auto_grow());
+
+        other.insert(&"not logged");
+        filter.union_in_place(&other).unwrap();
+        assert!(!filter.auto_grow());
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let mut a = BloomFilter::new_with_seeds(500, 5000, [(1, 2), (3, 4)]);
+        let mut b = empty_copy(&a);
+        a.extend(0..500);
+        b.extend(0..500);
+        assert_eq!(Ok(true), a.approx_eq(&b, 64));
+
+        let mut c = empty_copy(&a);
+        c.extend(500..1000);
+        assert_eq!(Ok(false), a.approx_eq(&c, 64));
+        assert_eq!(Ok(true), a.approx_eq(&c, 0));
+
+        let d = BloomFilter::new_with_size(500, 5000);
+        assert_eq!(Err(MergeError::HasherMismatch), a.approx_eq(&d, 64));
+    }
+
+    #[test]
+    fn test_hasher_independence_score() {
+        let sample: Vec<u32> = (0..1000).collect();
+
+        let filter = BloomFilter::new_with_seeds(100, 1000, [(1, 2), (3, 4)]);
+        assert!(filter.hasher_independence_score(&sample) > 0.99);
+        assert_eq!(1., filter.hasher_independence_score::<u32>(&[]));
+
+        // the second hash only spaces the indexes out, so identical keys
+        // still give independent indexes
+        let same = BloomFilter::new_with_seeds(100, 1000, [(1, 2), (1, 2)]);
+        assert!(same.hasher_independence_score(&sample) > 0.99);
+
+        // a second hash of zero puts all of a member's indexes in one bucket
+        let mut states = vec![FixedState(42), FixedState(0)].into_iter();
+        let stuck = BloomFilter::new_with_size_and_hasher(100, 1000, || {
+            states.next().unwrap()
+        });
+        assert!(stuck.n_hashers() > 1);
+        assert_eq!(0., stuck.hasher_independence_score(&sample));
+    }
+
+    #[test]
+    fn test_expected_set_bits() {
+        let mut filter = BloomFilter::new_with_fp(1000, 0.01);
+        assert_eq!(0., filter.expected_set_bits());
+
+        filter.extend(0..1000);
+        let expected = filter.expected_set_bits();
+        let observed = filter.set_bits() as f32;
+        assert!((observed - expected).abs() < 0.05 * expected);
+    }
+
+    #[test]
+    fn test_new_with_fp_checked() {
+        let (filter, achieved) =
+            BloomFilter::new_with_fp_checked(1000, 0.01).unwrap();
+        assert!((achieved - 0.01).abs() < 0.0005);
+        assert_eq!(achieved, filter.projected_fp_rate(1000));
+        assert_eq!(0.01, filter.design_fp_rate());
+
+        assert_eq!(
+            Err(BuildError::InvalidFpRate),
+            BloomFilter::new_with_fp_checked(1000, 1.).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_insert_parts() {
+        let mut filter =
+            BloomFilter::new_with_seeds(100, 1000, [(1, 2), (3, 4)]);
+        let (user, resource) = (17u64, "report.pdf");
+
+        filter.insert_parts(&[&user, &resource]);
+        assert!(filter.may_contain(&(user, resource)));
+        assert!(filter.may_contain_parts(&[&user, &resource]));
+        assert!(!filter.may_contain_parts(&[&resource, &user]));
+        assert!(!filter.may_contain_parts(&[&user]));
+
+        filter.insert(&(18u64, "other.pdf"));
+        assert!(filter.may_contain_parts(&[&18u64, &"other.pdf"]));
+        assert_eq!(
+            filter.indexes(&(user, resource)).collect::<Vec<_>>(),
+            filter
+                .indexes(&Parts(&[&user, &resource]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut filter = BloomFilter::new_with_fp(1000, 0.01);
+        filter.extend(0..500);
+        let mut snapshot = filter.clone();
+
+        filter.extend(500..1000);
+        let diff = filter.diff(&snapshot).unwrap();
+        assert!(!diff.is_empty());
+        assert!(diff.windows(2).all(|w| w[0] < w[1]));
+        assert!(diff.iter().all(|&idx| !snapshot.as_bitvec()[idx]));
+        assert_eq!(filter.set_bits() - snapshot.set_bits(), diff.len());
+
+        snapshot.apply_diff(&diff);
+        assert_eq!(filter, snapshot);
+        assert_eq!(Ok(Vec::new()), filter.diff(&snapshot));
+
+        let other = BloomFilter::new_with_fp(1000, 0.01);
+        assert_eq!(Err(MergeError::HasherMismatch), filter.diff(&other));
+    }
+
+    #[test]
+    fn test_apply_diff_stops_auto_grow() {
+        let mut filter = auto_growing(100, 0.01);
+        let mut source = filter.clone();
+        source.extend(0..50u32);
+
+        filter.apply_diff(&source.diff(&filter).unwrap());
+        assert!(!filter.auto_grow());
+
+        let buckets = filter.buckets();
+        filter.extend(50..1000u32);
+        assert_eq!(buckets, filter.buckets());
+        testing::assert_no_false_negatives(
+            &filter,
+            &(0..1000u32).collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apply_diff_out_of_range() {
+        let mut filter = BloomFilter::new_with_size(10, 100);
+        filter.apply_diff(&[100]);
+    }
+
+    #[test]
+    fn test_occupancy_map_is_uniform() {
+        let mut filter =
+            BloomFilter::new_with_seeds(10_000, 100_000, [(1, 2), (3, 4)]);
+        filter.extend(0..10_000);
+
+        let map = filter.occupancy_map(10);
+        assert_eq!(10, map.len());
+        let load_factor = filter.load_factor();
+        assert!(map.iter().all(|&f| (f - load_factor).abs() < 0.02));
+
+        assert!(filter.occupancy_map(0).is_empty());
+        assert_eq!(100_000, filter.occupancy_map(1_000_000).len());
+    }
+
+    #[test]
+    fn test_occupancy_map_shows_clustering() {
+        // unmixed double hashing of small hashes only reaches low buckets
+        let mut offset = 0;
+        let mut filter =
+            BloomFilter::new_with_size_and_hasher(1000, 10_000, || {
+                offset += 1;
+                OffsetState(offset)
+            });
+        filter.hashers =
+            filter.hashers.with_strategy(IndexStrategy::DoubleHash);
+        filter.extend(0..100u64);
+
+        let map = filter.occupancy_map(10);
+        assert!(map[0] > 0.);
+        assert!(map[1..].iter().all(|&f| f == 0.));
+    }
+
+    /// Test that `buckets` reports the addressable length rather than the
+    /// rounded-up storage capacity
+    #[test]
+    fn test_buckets_is_requested_size() {
+        let filter = BloomFilter::new_with_size(10, 37);
+        assert_eq!(37, filter.buckets());
+    }
+
+    /// Create an empty filter that hashes identically to `filter`
+    fn empty_copy(filter: &BloomFilter) -> BloomFilter {
+        BloomFilter {
+            size: 0,
+            buffer: BitVec::from_elem(filter.buckets(), false),
+            capacity: filter.capacity,
+            design_fp_rate: filter.design_fp_rate,
+            hashers: filter.hashers.clone(),
+            grow_log: None,
+        }
+    }
+
+    #[test]
+    fn test_union() {
+        let mut a = BloomFilter::new_with_fp(2, 0.01);
+        let mut b = empty_copy(&a);
+        a.insert(&"in a");
+        b.insert(&"in b");
+
+        let union = a.union(&b).unwrap();
+        assert!(union.may_contain(&"in a"));
+        assert!(union.may_contain(&"in b"));
+        assert_eq!(2, union.size());
+    }
+
+    #[test]
+    fn test_union_bucket_mismatch() {
+        let a = BloomFilter::new_with_size(2, 100);
+        let b = BloomFilter::new_with_size(2, 101);
+
+        assert_eq!(Err(MergeError::BucketMismatch), a.union(&b).map(|_| ()));
+    }
+
+    #[test]
+    fn test_union_hasher_mismatch() {
+        let a = BloomFilter::new_with_size(2, 100);
+        let b = BloomFilter::new_with_size(2, 100);
+
+        assert_eq!(Err(MergeError::HasherMismatch), a.union(&b).map(|_| ()));
+    }
+
+    #[test]
+    fn test_union_in_place() {
+        let mut a = BloomFilter::new_with_fp(2, 0.01);
+        let mut b = empty_copy(&a);
+        a.insert(&"in a");
+        b.insert(&"in b");
+
+        let storage = a.buffer.storage().as_ptr();
+        a.union_in_place(&b).unwrap();
+        assert_eq!(storage, a.buffer.storage().as_ptr());
+
+        assert!(a.may_contain(&"in a"));
+        assert!(a.may_contain(&"in b"));
+        assert_eq!(2, a.size());
+
+        let mut c = BloomFilter::new_with_fp(2, 0.01);
+        assert_eq!(Err(MergeError::HasherMismatch), c.union_in_place(&a));
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn test_union_all() {
+        let first = BloomFilter::new_with_fp(1000, 0.01);
+        let filters = (0..10).map(|i| {
+            let mut filter = empty_copy(&first);
+            filter.extend(i * 100..(i + 1) * 100);
+            filter
+        });
+
+        let union = BloomFilter::union_all(filters).unwrap();
+        testing::assert_no_false_negatives(
+            &union,
+            &(0..1000).collect::<Vec<_>>(),
+        );
+        assert_eq!(1000, union.size());
+    }
+
+    #[test]
+    fn test_union_all_mismatch() {
+        let a = BloomFilter::new_with_size(2, 100);
+        let b = empty_copy(&a);
+        let c = BloomFilter::new_with_size(2, 100);
+
+        assert_eq!(
+            Err(MergeError::HasherMismatch),
+            BloomFilter::union_all(vec![a, b, c]).map(|_| ())
+        );
+        assert_eq!(
+            Err(MergeError::NoFilters),
+            BloomFilter::<SipState>::union_all(vec![]).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_intersection() {
+        let mut a = BloomFilter::new_with_size(2, 1000);
+        let mut b = empty_copy(&a);
+        a.insert(&"in both");
+        a.insert(&"in a");
+        b.insert(&"in both");
+        b.insert(&"in b");
+
+        let intersection = a.intersection(&b).unwrap();
+        assert!(intersection.may_contain(&"in both"));
+        assert!(!intersection.may_contain(&"in a"));
+        assert!(!intersection.may_contain(&"in b"));
+        assert_eq!(2, intersection.size());
+    }
+
+    #[test]
+    fn test_intersection_hasher_mismatch() {
+        let a = BloomFilter::new_with_size(2, 100);
+        let b = BloomFilter::new_with_size(2, 100);
+
+        assert_eq!(
+            Err(MergeError::HasherMismatch),
+            a.intersection(&b).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_is_subset_of() {
+        let mut subset =
+            BloomFilter::new_with_seeds(100, 1000, [(1, 2), (3, 4)]);
+        let mut superset = empty_copy(&subset);
+        subset.extend(0..50);
+        superset.extend(0..100);
+
+        assert_eq!(Ok(true), subset.is_subset_of(&superset));
+        assert_eq!(Ok(false), superset.is_subset_of(&subset));
+
+        subset.insert(&"only in subset");
+        assert_eq!(Ok(false), subset.is_subset_of(&superset));
+
+        let other = BloomFilter::new_with_size(100, 1000);
+        assert_eq!(
+            Err(MergeError::HasherMismatch),
+            subset.is_subset_of(&other)
+        );
+    }
+
+    #[test]
+    fn test_estimated_union_cardinality() {
+        let mut a = BloomFilter::new_with_fp(1500, 0.01);
+        let mut b = empty_copy(&a);
+        a.extend(0..1000);
+        b.extend(500..1500);
+
+        let estimate = a.estimated_union_cardinality(&b).unwrap();
+        assert!((estimate as f32 - 1500.).abs() < 75.);
+        assert_eq!(a.union(&b).unwrap().estimated_cardinality(), estimate);
+
+        let other = BloomFilter::new_with_fp(1500, 0.01);
+        assert_eq!(
+            Err(MergeError::HasherMismatch),
+            a.estimated_union_cardinality(&other)
+        );
+    }
+
+    #[test]
+    fn test_estimated_symmetric_difference() {
+        let mut a = BloomFilter::new_with_fp(1100, 0.01);
+        let mut b = empty_copy(&a);
+        a.extend(0..1000);
+        b.extend(100..1100);
+
+        // 100 only in a and 100 only in b
+        let estimate = a.estimated_symmetric_difference(&b).unwrap() as f32;
+        assert!((estimate - 200.).abs() < 60.);
+
+        assert_eq!(Ok(0), a.estimated_symmetric_difference(&a.clone()));
+    }
+
+    #[test]
+    fn test_estimated_jaccard() {
+        let mut a = BloomFilter::new_with_fp(1500, 0.01);
+        let mut b = empty_copy(&a);
+        a.extend(0..1000);
+        b.extend(500..1500);
+
+        // 500 shared out of 1500 distinct
+        let jaccard = a.estimated_jaccard(&b).unwrap();
+        assert!((jaccard - 1. / 3.).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_estimated_jaccard_bounds() {
+        let mut a = BloomFilter::new_with_fp(100, 0.01);
+        let mut b = empty_copy(&a);
+        assert_eq!(Ok(1.), a.estimated_jaccard(&b));
+
+        a.extend(0..100);
+        assert!(a.estimated_jaccard(&a).unwrap() > 0.99);
+        assert!(a.estimated_jaccard(&b).unwrap() < 0.01);
+
+        b.extend(100..200);
+        assert!(a.estimated_jaccard(&b).unwrap() < 0.1);
+    }
+
+    #[test]
+    fn test_estimated_jaccard_mismatch() {
+        let a = BloomFilter::new_with_size(2, 100);
+        let b = BloomFilter::new_with_size(2, 100);
+
+        assert_eq!(Err(MergeError::HasherMismatch), a.estimated_jaccard(&b));
+    }
+
+    #[test]
+    fn test_resize_to_keeps_members() {
+        let mut filter = BloomFilter::new_with_size(100, 4000);
+        for e in 0..100 {
+            filter.insert(&e);
+        }
+
+        let resized = filter.resize_to(1000).unwrap();
+        assert_eq!(1000, resized.buckets());
+        assert_eq!(filter.size(), resized.size());
+        assert!((0..100).all(|e| resized.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut filter = BloomFilter::new_with_size(10_000, 96_000);
+        filter.extend(0..100);
+        filter.shrink_to_fit();
+
+        assert!(filter.buckets() < 96_000);
+        assert!(filter.fp_rate() <= filter.design_fp_rate());
+        assert_eq!(filter.size(), filter.capacity());
+        assert!((0..100).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_shrink_to_fit_when_full() {
+        let mut filter = BloomFilter::new_with_size(100, 1000);
+        filter.extend(0..100);
+        filter.shrink_to_fit();
+
+        assert_eq!(1000, filter.buckets());
+    }
+
+    #[test]
+    fn test_resize_to_indivisible() {
+        let filter = BloomFilter::new_with_size(10, 100);
+
+        assert_eq!(
+            Err(MergeError::IndivisibleBuckets),
+            filter.resize_to(30).map(|_| ())
+        );
+        assert_eq!(
+            Err(MergeError::IndivisibleBuckets),
+            filter.resize_to(0).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_union_after_resize() {
+        let seeds = [(1, 2), (3, 4)];
+        let mut small = BloomFilter::new_with_seeds(10, 100, seeds);
+        let mut large = BloomFilter::new_with_seeds(10, 300, seeds);
+        large.hashers = small.hashers.clone();
+        small.insert(&"in small");
+        large.insert(&"in large");
+
+        let union = large.resize_to(100).unwrap().union(&small).unwrap();
+        assert!(union.may_contain(&"in small"));
+        assert!(union.may_contain(&"in large"));
+    }
+
+    #[test]
+    fn test_same_seeds_same_indexes() {
+        let seeds = [(1, 2), (3, 4)];
+        let a = BloomFilter::new_with_seeds(10, 100, seeds);
+        let b = BloomFilter::new_with_seeds(10, 100, seeds);
+
+        assert!(a.indexes(&"do add this").eq(b.indexes(&"do add this")));
+        assert!(a.union(&b).is_ok());
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_new_with_rng() {
+        use rand_core::SeedableRng;
+        use rand_pcg::Pcg64;
+
+        let a =
+            BloomFilter::new_with_rng(100, 0.01, &mut Pcg64::seed_from_u64(7));
+        let b =
+            BloomFilter::new_with_rng(100, 0.01, &mut Pcg64::seed_from_u64(7));
+        let c =
+            BloomFilter::new_with_rng(100, 0.01, &mut Pcg64::seed_from_u64(8));
+
+        let indexes = |filter: &BloomFilter| {
+            filter.indexes(&"reproducible").collect::<Vec<_>>()
+        };
+        assert_eq!(indexes(&a), indexes(&b));
+        assert_ne!(indexes(&a), indexes(&c));
+        assert_eq!(a, b);
+    }
+
+    #[cfg(feature = "serde")]
+    fn assert_same_membership(a: &BloomFilter, b: &BloomFilter) {
+        assert_eq!(a.size(), b.size());
+        for e in 0..100 {
+            assert_eq!(a.may_contain(&e), b.may_contain(&e));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_round_trip() {
+        let mut filter = BloomFilter::new_with_fp(10, 0.01);
+        for e in 0..10 {
+            filter.insert(&e);
+        }
+
+        let json = serde_json::to_string(&filter).unwrap();
+        let decoded: BloomFilter = serde_json::from_str(&json).unwrap();
+
+        assert_same_membership(&filter, &decoded);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_bincode_round_trip() {
+        let mut filter = BloomFilter::new_with_fp(10, 0.01);
+        for e in 0..10 {
+            filter.insert(&e);
+        }
+
+        let bytes = bincode::serialize(&filter).unwrap();
+        let decoded: BloomFilter = bincode::deserialize(&bytes).unwrap();
+
+        assert_same_membership(&filter, &decoded);
+    }
+
+    /// Test that a clone keeps the original's hashers rather than getting new
+    /// ones
+    #[test]
+    fn test_clone_has_same_hashers() {
+        let mut filter = BloomFilter::new_with_fp(10, 0.01);
+        for e in 0..10 {
+            filter.insert(&e);
+        }
+
+        let clone = filter.clone();
+        assert_eq!(filter.size(), clone.size());
+        for e in 0..100 {
+            assert_eq!(filter.may_contain(&e), clone.may_contain(&e));
+            assert!(filter.indexes(&e).eq(clone.indexes(&e)));
+        }
+    }
+
+    #[test]
+    fn test_eq_same_seeds_same_members() {
+        let seeds = [(1, 2), (3, 4)];
+        let mut a = BloomFilter::new_with_seeds(10, 100, seeds);
+        let mut b = BloomFilter::new_with_seeds(10, 100, seeds);
+        for e in 0..10 {
+            a.insert(&e);
+            b.insert(&e);
+        }
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_hash_matches_eq() {
+        use std::collections::HashMap;
+
+        let seeds = [(1, 2), (3, 4)];
+        let mut a = BloomFilter::new_with_seeds(10, 100, seeds);
+        let mut b = BloomFilter::new_with_seeds(10, 100, seeds);
+        a.extend(0..10);
+        b.extend((0..10).rev());
+
+        let mut cache = HashMap::new();
+        cache.insert(a, "cached");
+        assert_eq!(Some(&"cached"), cache.get(&b));
+
+        b.insert(&"only in b");
+        assert_eq!(None, cache.get(&b));
+    }
+
+    #[test]
+    fn test_ne() {
+        let seeds = [(1, 2), (3, 4)];
+        let mut a = BloomFilter::new_with_seeds(10, 100, seeds);
+        let b = a.clone();
+        a.insert(&"only in a");
+        assert_ne!(a, b);
+
+        let unseeded = BloomFilter::new_with_size(10, 100);
+        assert_ne!(b, unseeded);
+        assert_ne!(b, BloomFilter::new_with_seeds(10, 101, seeds));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut filter = BloomFilter::new_with_seeds(2, 100, [(1, 2), (3, 4)]);
+        filter.insert(&"do add this");
+        let set_bits = filter.set_bits();
+
+        let summary = filter.to_string();
+        assert!(summary.contains("size: 1,"));
+        assert!(summary.contains("buckets: 100,"));
+        assert!(summary.contains(&format!("hashers: {},", filter.n_hashers())));
+        assert!(summary.contains(&format!("set bits: {},", set_bits)));
+        assert!(summary
+            .contains(&format!("load factor: {:.3},", set_bits as f32 / 100.)));
+        assert!(summary.contains("fp rate: "));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut filter = BloomFilter::new_with_size(2, 100);
+        let idxs: Vec<_> = filter.indexes(&"do add this").collect();
+        filter.insert(&"do add this");
+        filter.insert(&"and this");
+
+        filter.clear();
+        assert_eq!(0, filter.size());
+        assert_eq!(100, filter.buckets());
+        assert!(!filter.may_contain(&"do add this"));
+        assert!(!filter.may_contain(&"and this"));
+
+        filter.insert(&"do add this");
+        assert!(filter.indexes(&"do add this").eq(idxs));
+        assert!(filter.may_contain(&"do add this"));
+    }
+
+    /// Test that indexes are derived from the supplied hasher
+    #[test]
+    fn test_custom_hasher() {
+        let mut offset = 0;
+        let mut filter = BloomFilter::new_with_size_and_hasher(50, 100, || {
+            offset += 1;
+            OffsetState(offset)
+        });
+        filter.hashers =
+            filter.hashers.with_strategy(IndexStrategy::DoubleHash);
+
+        assert_eq!(2, filter.n_hashers());
+        assert!(filter.indexes(&7u64).eq(vec![8, 17]));
+        assert!(filter.indexes(&99u64).eq(vec![0, 1]));
+
+        filter.insert(&7u64);
+        assert!(filter.may_contain(&7u64));
+        assert!(!filter.may_contain(&9u64));
+    }
+
+    #[test]
+    fn test_from_parts_round_trip() {
+        let mut filter = BloomFilter::new_with_seeds(10, 100, [(1, 2), (3, 4)]);
+        for e in 0..10 {
+            filter.insert(&e);
+        }
+
+        let rebuilt = BloomFilter::from_parts(
+            filter.buffer.clone(),
+            filter.size(),
+            *filter.hashers.states(),
+            filter.n_hashers(),
+        )
+        .unwrap();
+        assert_eq!(filter, rebuilt);
+        assert_eq!(filter.size(), rebuilt.size());
+        assert!((0..10).all(|e| rebuilt.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_from_parts_invalid() {
+        let states = [SipState::new(), SipState::new()];
+
+        assert_eq!(
+            Err(BuildError::NoBuckets),
+            BloomFilter::from_parts(BitVec::new(), 0, states, 3).map(|_| ())
+        );
+        assert_eq!(
+            Err(BuildError::NoHashers),
+            BloomFilter::from_parts(BitVec::from_elem(10, false), 0, states, 0)
+                .map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_invalid_fp_rate() {
+        for &fp_rate in &[0.0, 1.0, -0.5, 1.5, f32::NAN] {
+            assert_eq!(
+                Err(BuildError::InvalidFpRate),
+                BloomFilter::try_new_with_fp(10, fp_rate).map(|_| ())
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_fp_rate_panics() {
+        BloomFilter::new_with_fp(10, 0.0);
+    }
+
+    #[test]
+    fn test_zero_size_does_not_panic() {
+        let mut filter = BloomFilter::new_with_size(1, 0);
+        assert_eq!(1, filter.buckets());
+
+        filter.insert(&"do add this");
+        assert!(filter.may_contain(&"do add this"));
+    }
+
+    #[test]
+    fn test_high_fp_rate_does_not_panic() {
+        let mut filter = BloomFilter::new_with_fp(1, 0.99);
+        assert!(filter.buckets() >= 1);
+
+        filter.insert(&"do add this");
+        assert!(filter.may_contain(&"do add this"));
     }
 }
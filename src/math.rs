@@ -0,0 +1,54 @@
+//! Floating point functions that `core` doesn't provide
+//!
+//! They come from the standard library when the `std` feature is enabled,
+//! and from `libm` otherwise.
+
+#[cfg(feature = "std")]
+pub fn ln(x: f32) -> f32 {
+    x.ln()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn ln(x: f32) -> f32 {
+    libm::logf(x)
+}
+
+#[cfg(feature = "std")]
+pub fn exp(x: f32) -> f32 {
+    x.exp()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn exp(x: f32) -> f32 {
+    libm::expf(x)
+}
+
+#[cfg(feature = "std")]
+pub fn powf(x: f32, n: f32) -> f32 {
+    x.powf(n)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn powf(x: f32, n: f32) -> f32 {
+    libm::powf(x, n)
+}
+
+#[cfg(feature = "std")]
+pub fn ceil(x: f32) -> f32 {
+    x.ceil()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn ceil(x: f32) -> f32 {
+    libm::ceilf(x)
+}
+
+#[cfg(feature = "std")]
+pub fn round(x: f32) -> f32 {
+    x.round()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn round(x: f32) -> f32 {
+    libm::roundf(x)
+}
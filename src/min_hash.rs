@@ -0,0 +1,153 @@
+//! Similarity estimates using the same hashing as the filters
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use super::{seeded, Hashers, MergeError, SipState};
+
+/// Sketch of a set for estimating how similar it is to others
+///
+/// Keeps the smallest of each of `n_hashers` hashes over every member. Two
+/// sets agree on a minimum with probability equal to their Jaccard index, so
+/// the fraction of minimums two sketches share estimates it, with a standard
+/// error of about `1 / sqrt(n_hashers)`. Only sketches built with the same
+/// hashers, e.g. from the same seeds or by cloning an empty one, can be
+/// compared.
+#[derive(Debug, Clone)]
+pub struct MinHash {
+    mins: Vec<u64>,
+    hashers: Hashers<SipState>,
+}
+
+impl MinHash {
+    /// Create a new MinHash sketch
+    ///
+    /// # Arguments
+    /// * `n_hashers`: number of minimums to keep, rounded up to 1 if zero
+    pub fn new(n_hashers: usize) -> MinHash {
+        let n_hashers = n_hashers.max(1);
+
+        MinHash {
+            mins: vec![u64::MAX; n_hashers],
+            hashers: Hashers::new(n_hashers, SipState::new),
+        }
+    }
+
+    /// Create a new MinHash sketch whose hashers are built from known keys
+    ///
+    /// # Arguments
+    /// * `n_hashers`: number of minimums to keep, rounded up to 1 if zero
+    /// * `seeds`: keys for the two underlying hashers, see
+    ///   `SipState::with_keys`
+    pub fn new_with_seeds(n_hashers: usize, seeds: [(u64, u64); 2]) -> MinHash {
+        let n_hashers = n_hashers.max(1);
+
+        MinHash {
+            mins: vec![u64::MAX; n_hashers],
+            hashers: Hashers::new(n_hashers, seeded(seeds)),
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T)
+    where
+        T: Hash,
+    {
+        for (min, h) in self.mins.iter_mut().zip(self.hashers.hashes(e)) {
+            *min = (*min).min(h);
+        }
+    }
+
+    /// Estimate the Jaccard index of the sets inserted into two sketches
+    ///
+    /// Two empty sketches are considered identical.
+    ///
+    /// # Arguments
+    /// * `other`: sketch to compare with, must have the same hashers
+    pub fn estimated_similarity(
+        &self,
+        other: &MinHash,
+    ) -> Result<f32, MergeError> {
+        if !self.hashers.same_as(&other.hashers) {
+            return Err(MergeError::HasherMismatch);
+        }
+
+        let matching = self
+            .mins
+            .iter()
+            .zip(&other.mins)
+            .filter(|(a, b)| a == b)
+            .count();
+
+        Ok(matching as f32 / self.n_hashers() as f32)
+    }
+
+    /// Number of minimums kept
+    pub fn n_hashers(&self) -> usize {
+        self.mins.len()
+    }
+
+    /// Whether no elements have been inserted
+    pub fn is_empty(&self) -> bool {
+        self.mins.iter().all(|&min| min == u64::MAX)
+    }
+
+    /// Remove every member, keeping the hashers
+    pub fn clear(&mut self) {
+        for min in &mut self.mins {
+            *min = u64::MAX;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SEEDS: [(u64, u64); 2] = [(1, 2), (3, 4)];
+
+    #[test]
+    fn test_estimated_similarity() {
+        let mut a = MinHash::new_with_seeds(256, SEEDS);
+        let mut b = a.clone();
+        for e in 0..1000 {
+            a.insert(&e);
+        }
+        for e in 500..1500 {
+            b.insert(&e);
+        }
+
+        // 500 shared out of 1500 distinct
+        let similarity = a.estimated_similarity(&b).unwrap();
+        assert!((similarity - 1. / 3.).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_estimated_similarity_bounds() {
+        let mut a = MinHash::new(64);
+        let mut b = a.clone();
+        assert!(a.is_empty());
+        assert_eq!(Ok(1.), a.estimated_similarity(&b));
+
+        for e in 0..100 {
+            a.insert(&e);
+            b.insert(&(e + 1000));
+        }
+        assert_eq!(Ok(1.), a.estimated_similarity(&a.clone()));
+        assert!(a.estimated_similarity(&b).unwrap() < 0.1);
+
+        a.clear();
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn test_hasher_mismatch() {
+        let a = MinHash::new_with_seeds(64, SEEDS);
+        let b = MinHash::new_with_seeds(64, [(5, 6), (7, 8)]);
+
+        assert_eq!(Err(MergeError::HasherMismatch), a.estimated_similarity(&b));
+    }
+}
@@ -0,0 +1,205 @@
+//! A Bloom Filter variant giving every hasher its own slice of buckets
+
+use core::hash::Hash;
+
+use bit_vec::BitVec;
+
+use super::{
+    check_fp_rate, false_positive_rate, min_n_buckets, optimal_n_hashers,
+};
+use super::{indexes_are_set, set_indexes, Hashers, SipState};
+
+/// Bloom Filter whose buckets are split evenly between its hashers
+///
+/// Hasher `i` only ever sets buckets in slice `i`, so a member always sets
+/// exactly one bucket per slice and the indexes of a single member can never
+/// collide with each other.
+#[derive(Debug, Clone)]
+pub struct PartitionedBloomFilter {
+    buffer: BitVec,
+    size: usize,
+    slice_len: usize,
+    hashers: Hashers<SipState>,
+}
+
+impl PartitionedBloomFilter {
+    /// Build a Partitioned Bloom Filter with a specified false positive rate
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `fp_rate`: desired false positive rate (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new_with_fp(n_elems: usize, fp_rate: f32) -> PartitionedBloomFilter {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        PartitionedBloomFilter::new_with_size(
+            n_elems,
+            min_n_buckets(n_elems, fp_rate),
+        )
+    }
+
+    /// Create a new Partitioned Bloom Filter with specified number of buckets
+    ///
+    /// # Arguments
+    /// * `n_elems`: expected number of elements
+    /// * `size`: desired number of buckets, rounded up to a multiple of the
+    ///   number of hashers
+    pub fn new_with_size(
+        n_elems: usize,
+        size: usize,
+    ) -> PartitionedBloomFilter {
+        let size = size.max(1);
+        let n_hashers = optimal_n_hashers(size, n_elems);
+        let slice_len = size.div_ceil(n_hashers);
+
+        PartitionedBloomFilter {
+            buffer: BitVec::from_elem(slice_len * n_hashers, false),
+            size: 0,
+            slice_len,
+            hashers: Hashers::new(n_hashers, SipState::new),
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// Returns whether any of the member's buckets were previously unset,
+    /// i.e. whether it was definitely not a member before.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        let indexes = self.indexes(e);
+        let inserted = set_indexes(&mut self.buffer, indexes);
+
+        if inserted {
+            self.size += 1;
+        }
+
+        inserted
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        indexes_are_set(&self.buffer, self.indexes(e))
+    }
+
+    /// Remove all members
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.size = 0;
+    }
+
+    /// Approximate number of distinct elements in the `PartitionedBloomFilter`
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether no elements have been inserted
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Number of buckets across all slices
+    pub fn buckets(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Number of buckets in each hasher's slice
+    pub fn slice_len(&self) -> usize {
+        self.slice_len
+    }
+
+    /// Number of hashers, which is also the number of slices
+    pub fn n_hashers(&self) -> usize {
+        self.hashers.len()
+    }
+
+    /// False positive rate
+    pub fn fp_rate(&self) -> f32 {
+        false_positive_rate(self.buckets(), self.n_hashers(), self.size)
+    }
+
+    /// The indexes that an element hashes to, the `i`th within slice `i`
+    fn indexes<T>(&self, e: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        let slice_len = self.slice_len;
+
+        self.hashers
+            .indexes(e, slice_len)
+            .enumerate()
+            .map(move |(i, idx)| i * slice_len + idx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_fp_out_of_range() {
+        PartitionedBloomFilter::new_with_fp(10, 1.0);
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let mut filter = PartitionedBloomFilter::new_with_fp(10_000, 0.01);
+        for e in 0..10_000 {
+            filter.insert(&e);
+        }
+
+        assert!((0..10_000).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_absent_members() {
+        let mut filter = PartitionedBloomFilter::new_with_fp(10_000, 0.01);
+        for e in 0..10_000 {
+            filter.insert(&e);
+        }
+
+        let false_positives =
+            (10_000..110_000).filter(|e| filter.may_contain(e)).count();
+        assert!((false_positives as f32 / 100_000.) < 0.02);
+    }
+
+    #[test]
+    fn test_indexes_fall_in_own_slice() {
+        let filter = PartitionedBloomFilter::new_with_fp(100, 0.01);
+        let slice_len = filter.slice_len();
+
+        for e in 0..100 {
+            let idxs: Vec<_> = filter.indexes(&e).collect();
+            assert_eq!(filter.n_hashers(), idxs.len());
+
+            for (i, idx) in idxs.into_iter().enumerate() {
+                assert!(idx >= i * slice_len && idx < (i + 1) * slice_len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_size_is_multiple_of_slices() {
+        let filter = PartitionedBloomFilter::new_with_size(10, 101);
+
+        assert!(filter.buckets() >= 101);
+        assert_eq!(filter.buckets(), filter.slice_len() * filter.n_hashers());
+    }
+}
@@ -0,0 +1,141 @@
+//! A Bloom Filter whose members expire
+
+use core::hash::Hash;
+use core::mem;
+
+use super::{check_fp_rate, BloomFilter};
+
+/// Probabilistic hash set that forgets members over a sliding window
+///
+/// Members are inserted into an active filter, and lookups check it along
+/// with the previous one. Each `rotate` discards the previous filter and
+/// starts a fresh active one, so a member is forgotten after at most two
+/// rotations. The filter rotates itself after every `interval` inserts, and
+/// can also be rotated by hand, e.g. on a timer.
+///
+/// Each filter is built for half the false positive rate, so the rate of
+/// both combined stays below the configured one.
+#[derive(Debug, Clone)]
+pub struct RotatingBloomFilter {
+    active: BloomFilter,
+    previous: BloomFilter,
+    interval: usize,
+    inserts: usize,
+}
+
+impl RotatingBloomFilter {
+    /// Build a Rotating Bloom Filter
+    ///
+    /// # Arguments
+    /// * `interval`: number of inserts between rotations, rounded up to 1 if
+    ///   zero
+    /// * `fp_rate`: false positive rate that is never exceeded (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new(interval: usize, fp_rate: f32) -> RotatingBloomFilter {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        let interval = interval.max(1);
+        RotatingBloomFilter {
+            active: BloomFilter::new_with_fp(interval, fp_rate / 2.),
+            previous: BloomFilter::new_with_fp(interval, fp_rate / 2.),
+            interval,
+            inserts: 0,
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// Returns whether at least one new bit was set in the active filter.
+    /// Rotates first if there have been `interval` inserts since the last
+    /// rotation.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        if self.inserts >= self.interval {
+            self.rotate();
+        }
+
+        self.inserts += 1;
+        self.active.insert(e)
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.active.may_contain(e) || self.previous.may_contain(e)
+    }
+
+    /// Discard the previous filter and start a fresh active one
+    pub fn rotate(&mut self) {
+        mem::swap(&mut self.active, &mut self.previous);
+        self.active.clear();
+        self.inserts = 0;
+    }
+
+    /// Number of inserts between rotations
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Approximate number of distinct elements in both filters
+    pub fn size(&self) -> usize {
+        self.active.size() + self.previous.size()
+    }
+
+    /// Whether no elements have been inserted since the last two rotations
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// False positive rate
+    ///
+    /// The probability that either filter reports a false positive.
+    pub fn fp_rate(&self) -> f32 {
+        1. - (1. - self.active.fp_rate()) * (1. - self.previous.fp_rate())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expires_after_two_rotations() {
+        let mut filter = RotatingBloomFilter::new(100, 0.01);
+        filter.insert(&"stale");
+
+        filter.rotate();
+        assert!(filter.may_contain(&"stale"));
+
+        filter.rotate();
+        filter.insert(&"fresh");
+        assert!(!filter.may_contain(&"stale"));
+        assert!(filter.may_contain(&"fresh"));
+    }
+
+    #[test]
+    fn test_rotates_every_interval() {
+        let mut filter = RotatingBloomFilter::new(10, 0.01);
+        for e in 0..30 {
+            filter.insert(&e);
+        }
+
+        // the last 10 are active and the 10 before them are previous
+        assert!((10..30).all(|e| filter.may_contain(&e)));
+        assert!(filter.size() <= 20);
+        assert!(filter.fp_rate() <= 0.01);
+    }
+}
@@ -0,0 +1,236 @@
+//! A Bloom Filter that grows as members are added
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use super::{check_fp_rate, BloomFilter};
+
+/// Factor by which each new filter's capacity grows
+const GROWTH_FACTOR: usize = 2;
+
+/// Factor by which each new filter's false positive rate shrinks
+///
+/// Almeida et al. suggest 0.8 to 0.9. Smaller ratios give the first filter a
+/// larger share of the rate, which a filter that hasn't grown much is then
+/// likely to exceed once rounding and hashing noise are added.
+const TIGHTENING_RATIO: f32 = 0.8;
+
+/// Probabilistic hash set that grows to keep its false positive rate bounded
+///
+/// Holds a chain of `BloomFilter`s (Almeida et al., "Scalable Bloom Filters").
+/// Members are inserted into the newest filter, and once it holds as many as
+/// it was sized for, a new one with `GROWTH_FACTOR` times the capacity and
+/// `TIGHTENING_RATIO` times the false positive rate is added. The false
+/// positive rates form a geometric series whose sum is the configured rate.
+#[derive(Debug, Clone)]
+pub struct ScalableBloomFilter {
+    stages: Vec<Stage>,
+}
+
+/// One filter in the chain, along with how many members it was sized for
+#[derive(Debug, Clone)]
+struct Stage {
+    filter: BloomFilter,
+    capacity: usize,
+    fp_rate: f32,
+}
+
+impl Stage {
+    fn new(capacity: usize, fp_rate: f32) -> Stage {
+        Stage {
+            filter: BloomFilter::new_with_fp(capacity, fp_rate),
+            capacity,
+            fp_rate,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.filter.size() >= self.capacity
+    }
+}
+
+impl ScalableBloomFilter {
+    /// Build a Scalable Bloom Filter
+    ///
+    /// # Arguments
+    /// * `initial_capacity`: number of elements the first filter is sized for,
+    ///   rounded up to 1 if zero
+    /// * `fp_rate`: false positive rate that is never exceeded (0.0 -> 1.0)
+    ///
+    /// # Panics
+    /// If `fp_rate` isn't strictly between 0.0 and 1.0
+    pub fn new(initial_capacity: usize, fp_rate: f32) -> ScalableBloomFilter {
+        if let Err(e) = check_fp_rate(fp_rate) {
+            panic!("{}", e);
+        }
+
+        let capacity = initial_capacity.max(1);
+        let stage = Stage::new(capacity, fp_rate * (1. - TIGHTENING_RATIO));
+
+        ScalableBloomFilter {
+            stages: vec![stage],
+        }
+    }
+
+    /// Insert a member
+    ///
+    /// Returns whether the member was definitely not present before. Members
+    /// that may already be present aren't inserted again, so they don't use
+    /// up capacity.
+    ///
+    /// # Arguments
+    /// * `e`: element to add
+    pub fn insert<T>(&mut self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        if self.may_contain(e) {
+            return false;
+        }
+
+        if self.newest().is_full() {
+            let stage = {
+                let newest = self.newest();
+                Stage::new(
+                    newest.capacity * GROWTH_FACTOR,
+                    newest.fp_rate * TIGHTENING_RATIO,
+                )
+            };
+            self.stages.push(stage);
+        }
+
+        let stages = self.stages.len();
+        self.stages[stages - 1].filter.insert(e)
+    }
+
+    /// Make room for at least `additional` more members in the newest filter
+    ///
+    /// Unless the newest filter can already hold them, a new one is added as
+    /// if it had filled up, but with at least `additional` capacity, so the
+    /// members are inserted without growing the chain one filter at a time.
+    /// A plain `BloomFilter` can't grow without its members, so this is only
+    /// offered here; see `BloomFilter::reprovision` for starting over.
+    ///
+    /// # Arguments
+    /// * `additional`: number of members about to be inserted
+    pub fn reserve(&mut self, additional: usize) {
+        let newest = self.newest();
+        let remaining = newest.capacity.saturating_sub(newest.filter.size());
+        if additional <= remaining {
+            return;
+        }
+
+        let stage = Stage::new(
+            additional.max(newest.capacity * GROWTH_FACTOR),
+            newest.fp_rate * TIGHTENING_RATIO,
+        );
+        self.stages.push(stage);
+    }
+
+    /// Check membership
+    ///
+    /// # Arguments
+    /// * `e`: element to check membership of
+    pub fn may_contain<T>(&self, e: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.stages.iter().any(|stage| stage.filter.may_contain(e))
+    }
+
+    /// Approximate number of distinct elements in the `ScalableBloomFilter`
+    pub fn size(&self) -> usize {
+        self.stages.iter().map(|stage| stage.filter.size()).sum()
+    }
+
+    /// Whether no elements have been inserted
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Number of filters in the chain
+    pub fn n_filters(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// False positive rate
+    ///
+    /// The probability that at least one filter in the chain reports a false
+    /// positive.
+    pub fn fp_rate(&self) -> f32 {
+        let all_negative: f32 = self
+            .stages
+            .iter()
+            .map(|stage| 1. - stage.filter.fp_rate())
+            .product();
+
+        1. - all_negative
+    }
+
+    /// The filter currently being inserted into
+    fn newest(&self) -> &Stage {
+        self.stages.last().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_grows() {
+        let mut filter = ScalableBloomFilter::new(10, 0.01);
+        assert_eq!(1, filter.n_filters());
+
+        for e in 0..100 {
+            filter.insert(&e);
+        }
+
+        assert!(filter.n_filters() > 1);
+        assert!((0..100).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_fp_rate_is_bounded() {
+        let mut filter = ScalableBloomFilter::new(100, 0.01);
+        for e in 0..10_000 {
+            filter.insert(&e);
+        }
+
+        assert!(filter.fp_rate() <= 0.01);
+
+        let false_positives =
+            (10_000..110_000).filter(|e| filter.may_contain(e)).count();
+        assert!((false_positives as f32 / 100_000.) < 0.01);
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut filter = ScalableBloomFilter::new(10, 0.01);
+        filter.reserve(5);
+        assert_eq!(1, filter.n_filters());
+
+        filter.reserve(10_000);
+        assert_eq!(2, filter.n_filters());
+        for e in 0..10_000 {
+            filter.insert(&e);
+        }
+
+        assert_eq!(2, filter.n_filters());
+        assert!(filter.fp_rate() <= 0.01);
+        assert!((0..10_000).all(|e| filter.may_contain(&e)));
+    }
+
+    #[test]
+    fn test_duplicates_do_not_use_capacity() {
+        let mut filter = ScalableBloomFilter::new(10, 0.01);
+
+        assert!(filter.insert(&"do add this"));
+        for _ in 0..100 {
+            assert!(!filter.insert(&"do add this"));
+        }
+
+        assert_eq!(1, filter.size());
+        assert_eq!(1, filter.n_filters());
+    }
+}
@@ -0,0 +1,210 @@
+//! Sizing math for Bloom Filters
+//!
+//! These answer how large a filter needs to be, and how it will perform,
+//! without building one. For example, a filter for 10 million elements at a
+//! false positive rate of 0.001 needs about 17 MiB:
+//!
+//! ```
+//! use bloom::sizing;
+//!
+//! let bytes = sizing::min_n_buckets(10_000_000, 0.001) / 8;
+//! assert!(bytes > 17_000_000 && bytes < 18_500_000);
+//! ```
+
+use core::f32::consts::LN_2;
+
+use super::math;
+
+/// Calculate the probability of getting a false positive
+///
+/// ```
+/// use bloom::sizing::false_positive_rate;
+///
+/// let fp_rate = false_positive_rate(9586, 7, 1000);
+/// assert!((fp_rate - 0.01).abs() < 0.001);
+/// ```
+///
+/// # Arguments
+/// * `n_buckets`: number of buckets
+/// * `n_hashers`: number of hashers
+/// * `n_elems`: number of elements
+pub fn false_positive_rate(
+    n_buckets: usize,
+    n_hashers: usize,
+    n_elems: usize,
+) -> f32 {
+    let k = n_hashers as f32;
+    let n = n_elems as f32;
+    let m = n_buckets as f32;
+
+    math::powf(1. - math::exp((-k * n) / m), k)
+}
+
+/// Minumum number of buckets required to achieve a target false positive rate
+///
+/// Always at least 1, so that the resulting buffer can be indexed into
+///
+/// ```
+/// use bloom::sizing::min_n_buckets;
+///
+/// assert_eq!(9586, min_n_buckets(1000, 0.01));
+/// ```
+///
+/// # Arguments
+/// * `n_elems`: target number of elements
+/// * `fp_rate`: target false positive rate
+pub fn min_n_buckets(n_elems: usize, fp_rate: f32) -> usize {
+    let n = n_elems as f32;
+
+    let buckets = math::ceil(-n * math::ln(fp_rate) / (LN_2 * LN_2)) as usize;
+    buckets.max(1)
+}
+
+/// Maximum number of elements that fit in some memory at a target false
+/// positive rate
+///
+/// The inverse of `min_n_buckets`, with 8 buckets per byte. For example,
+/// 4 MiB holds over 2.3 million elements at a rate of 0.1%:
+///
+/// ```
+/// use bloom::sizing::max_elements;
+///
+/// let n_elems = max_elements(4 * 1024 * 1024, 0.001);
+/// assert!(n_elems > 2_300_000 && n_elems < 2_400_000);
+/// ```
+///
+/// # Arguments
+/// * `max_bytes`: memory available for the buckets
+/// * `fp_rate`: target false positive rate
+pub fn max_elements(max_bytes: usize, fp_rate: f32) -> usize {
+    let m = max_bytes as f32 * 8.;
+
+    (m * LN_2 * LN_2 / -math::ln(fp_rate)) as usize
+}
+
+/// Calculate the optimal number of hashers
+///
+/// Always at least 1, since a filter without hashers can't tell members
+/// apart from anything else
+///
+/// ```
+/// use bloom::sizing::optimal_n_hashers;
+///
+/// assert_eq!(7, optimal_n_hashers(9586, 1000));
+/// ```
+///
+/// # Arguments
+/// * `n_buckets`: number of buckets
+/// * `n_elems`: number of elemements, treated as 1 if zero
+pub fn optimal_n_hashers(n_buckets: usize, n_elems: usize) -> usize {
+    let n = n_elems.max(1) as f32;
+    let m = n_buckets as f32;
+
+    (math::ceil((m / n) * LN_2) as usize).max(1)
+}
+
+/// Lowest false positive rate a number of buckets can achieve
+///
+/// The rate with the optimal number of hashers. For example, 1 MiB holds a
+/// million elements at a rate of less than 2%:
+///
+/// ```
+/// use bloom::sizing::best_fp_rate;
+///
+/// let fp_rate = best_fp_rate(8 * 1024 * 1024, 1_000_000);
+/// assert!(fp_rate > 0.017 && fp_rate < 0.018);
+/// ```
+///
+/// # Arguments
+/// * `n_buckets`: number of buckets
+/// * `n_elems`: number of elements
+pub fn best_fp_rate(n_buckets: usize, n_elems: usize) -> f32 {
+    let n_hashers = optimal_n_hashers(n_buckets, n_elems);
+    false_positive_rate(n_buckets, n_hashers, n_elems)
+}
+
+/// Number of elements at which a filter reaches a false positive rate
+///
+/// The inverse of `false_positive_rate`, rounded down. Saturates at
+/// `usize::MAX` for rates of 1 or more.
+///
+/// ```
+/// use bloom::sizing::max_elems_at_fp_rate;
+///
+/// let n_elems = max_elems_at_fp_rate(9586, 7, 0.01);
+/// assert!(n_elems > 950 && n_elems < 1050);
+/// ```
+///
+/// # Arguments
+/// * `n_buckets`: number of buckets
+/// * `n_hashers`: number of hashers
+/// * `fp_rate`: false positive rate
+pub fn max_elems_at_fp_rate(
+    n_buckets: usize,
+    n_hashers: usize,
+    fp_rate: f32,
+) -> usize {
+    let k = n_hashers as f32;
+    let m = n_buckets as f32;
+
+    let n = -(m / k) * math::ln(1. - math::powf(fp_rate.min(1.), 1. / k));
+    n as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Test against the usual worked example of 1000 elements at 1%, which
+    /// needs 9.6 bits per element and 7 hashers
+    #[test]
+    fn test_textbook_values() {
+        assert_eq!(9586, min_n_buckets(1000, 0.01));
+        assert_eq!(7, optimal_n_hashers(9586, 1000));
+
+        let fp_rate = false_positive_rate(9586, 7, 1000);
+        assert!((fp_rate - 0.01).abs() < 0.0005);
+    }
+
+    #[test]
+    fn test_best_fp_rate() {
+        let best = best_fp_rate(9586, 1000);
+        assert!((best - 0.01).abs() < 0.0005);
+
+        assert!(best < false_positive_rate(9586, 3, 1000));
+        assert!(best < false_positive_rate(9586, 12, 1000));
+    }
+
+    #[test]
+    fn test_max_elements() {
+        // 1000 elements at 1% need 9586 buckets, a little over 1198 bytes
+        assert_eq!(999, max_elements(1198, 0.01));
+        assert_eq!(1000, max_elements(1199, 0.01));
+
+        let n_elems = max_elements(4 * 1024 * 1024, 0.001);
+        assert_eq!(2_333_801, n_elems);
+        assert!(min_n_buckets(n_elems, 0.001) <= 4 * 1024 * 1024 * 8);
+    }
+
+    #[test]
+    fn test_max_elems_inverts_fp_rate() {
+        let n_elems = max_elems_at_fp_rate(9586, 7, 0.01);
+        assert!(false_positive_rate(9586, 7, n_elems) <= 0.01);
+        assert!(false_positive_rate(9586, 7, n_elems + 2) > 0.01);
+
+        assert_eq!(0, max_elems_at_fp_rate(9586, 7, 0.));
+        assert_eq!(usize::MAX, max_elems_at_fp_rate(9586, 7, 1.));
+    }
+
+    #[test]
+    fn test_no_elems() {
+        assert_eq!(1, min_n_buckets(0, 0.01));
+        assert_eq!(0., false_positive_rate(100, 3, 0));
+    }
+
+    #[test]
+    fn test_at_least_one_hasher() {
+        assert_eq!(1, optimal_n_hashers(10, 1_000_000));
+        assert_eq!(1, optimal_n_hashers(0, 10));
+    }
+}
@@ -0,0 +1,37 @@
+//! Helpers for testing code that uses filters
+
+use core::fmt::Debug;
+use core::hash::BuildHasher;
+use core::hash::Hash;
+
+use super::BloomFilter;
+
+/// Assert that a filter reports every inserted element as present
+///
+/// A Bloom Filter never gives false negatives, so this failing means the
+/// filter is broken, or wasn't built from `inserted`.
+///
+/// ```
+/// use bloom::testing::assert_no_false_negatives;
+/// use bloom::BloomFilter;
+///
+/// let inserted: Vec<u32> = (0..1000).collect();
+/// let filter: BloomFilter = inserted.iter().collect();
+/// assert_no_false_negatives(&filter, &inserted);
+/// ```
+///
+/// # Arguments
+/// * `filter`: filter to check
+/// * `inserted`: elements that were inserted into `filter`
+///
+/// # Panics
+/// If any element of `inserted` isn't reported as possibly present
+pub fn assert_no_false_negatives<S, T>(filter: &BloomFilter<S>, inserted: &[T])
+where
+    S: BuildHasher + Clone,
+    T: Hash + Debug,
+{
+    for e in inserted {
+        assert!(filter.may_contain(e), "false negative for {:?}", e);
+    }
+}